@@ -0,0 +1,45 @@
+use nalgebra as na;
+use amethyst::ecs::{Component, DenseVecStorage, Entity};
+use crate::config::BotSkill;
+
+/// A Component for tanks controlled by the computer.
+/// The BotSystem decides what to do and stores the controls here,
+/// the TankSystem then uses them the same way it uses a player's input
+pub struct Bot {
+    pub skill: BotSkill,
+
+    pub forward: f32,
+    pub side: f32,
+    pub fire: bool,
+
+    /// Time until the bot looks around again
+    pub reaction_timer: f32,
+    /// Points the bot is driving through
+    pub path: Vec<na::Point2<f32>>,
+    /// An enemy tank the bot can see and is aiming at
+    pub target: Option<Entity>,
+    /// How far off the bot's aim is, changes every time it reacts
+    pub aim_offset: f32,
+    /// Drive forward (1.0) or backward (-1.0) to get out of a bullet's way
+    pub dodge: Option<f32>,
+}
+
+impl Bot {
+    pub fn new(skill: BotSkill) -> Self {
+        Bot {
+            skill,
+            forward: 0.0,
+            side: 0.0,
+            fire: false,
+            reaction_timer: 0.0,
+            path: Vec::new(),
+            target: None,
+            aim_offset: 0.0,
+            dodge: None,
+        }
+    }
+}
+
+impl Component for Bot {
+    type Storage = DenseVecStorage<Self>;
+}
@@ -1,4 +1,7 @@
 use serde::{Serialize, Deserialize};
+use amethyst::input::{Bindings, StringBindings};
+use crate::utils::mazegen::MazeAlgorithm;
+use crate::tank::Team;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TankConfig {
@@ -14,6 +17,32 @@ pub struct TankConfig {
     pub sprite_nums: Vec<usize>,
 }
 
+/// Where the mazes come from
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum MazeSource {
+    /// Generate a new maze every round
+    Procedural,
+    /// Always play the same map from `res/levels/`
+    Fixed(String),
+    /// Play maps from `res/levels/` one after another
+    Playlist(Vec<String>),
+}
+impl Default for MazeSource {
+    fn default() -> Self {
+        MazeSource::Procedural
+    }
+}
+impl MazeSource {
+    /// Names of the maps from `res/levels/` that get played
+    pub fn map_names(&self) -> &[String] {
+        match self {
+            MazeSource::Procedural => &[],
+            MazeSource::Fixed(name) => std::slice::from_ref(name),
+            MazeSource::Playlist(names) => names,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MazeConfig {
     pub cell_width: f32,
@@ -21,11 +50,142 @@ pub struct MazeConfig {
     pub w_thickness: f32,
     pub maze_width: usize,
     pub maze_height: usize,
+    #[serde(default)]
+    pub source: MazeSource,
+    /// Seed for the first maze; every following maze is derived from it.
+    /// A random seed is used if this isn't set
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Algorithms to generate mazes with, rotated every round
+    #[serde(default)]
+    pub algorithms: Vec<MazeAlgorithm>,
+    /// Fraction of dead ends turned into loops, 0.0 - 1.0
+    #[serde(default)]
+    pub braid_factor: f32,
+    /// Optionally keep braiding until there are at most this many dead ends
+    #[serde(default)]
+    pub max_dead_ends: Option<usize>,
     //pub sprite_num: usize,
     //pub sprite_length: f32,
     //pub sprite_width: f32,
 }
 
+impl MazeConfig {
+    /// Check that a generated maze has a cell for every playing tank to start in
+    pub fn validate(&self, player_count: usize) -> Result<(), String> {
+        if self.source.map_names().is_empty() && self.maze_width * self.maze_height < player_count {
+            return Err(format!(
+                "A {}x{} maze doesn't have room for {} players",
+                self.maze_width, self.maze_height, player_count
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// How a single player is controlled: names of the axes and actions from `bindings.ron`,
+/// or a bot driving the tank instead
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PlayerConfig {
+    #[serde(default)]
+    pub forward: String,
+    #[serde(default)]
+    pub side: String,
+    #[serde(default)]
+    pub fire: String,
+    /// Let the computer control this player; bots don't need any bindings
+    #[serde(default)]
+    pub bot: Option<BotDifficulty>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PlayersConfig {
+    pub player_count: usize,
+    /// Controls for every player, in player order
+    pub players: Vec<PlayerConfig>,
+}
+
+impl PlayersConfig {
+    /// Check that there is a valid player count and that every playing
+    /// player's axes and actions are defined in the input bindings
+    pub fn validate(&self, bindings: &Bindings<StringBindings>) -> Result<(), String> {
+        if !(2..=Team::ALL.len()).contains(&self.player_count) {
+            return Err(format!("player_count has to be between 2 and {}", Team::ALL.len()));
+        }
+        if self.players.len() < self.player_count {
+            return Err(format!(
+                "{} players are playing, but only {} have bindings",
+                self.player_count, self.players.len()
+            ));
+        }
+        for (team, player) in Team::ALL.iter().zip(self.players.iter()).take(self.player_count) {
+            if player.bot.is_some() { continue; }
+            for axis in &[&player.forward, &player.side] {
+                if bindings.axis(*axis).is_none() {
+                    return Err(format!("{}: axis {} not defined", team.name(), axis));
+                }
+            }
+            if bindings.action_bindings(&player.fire).next().is_none() {
+                return Err(format!("{}: action {} not defined", team.name(), player.fire));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum BotDifficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+/// What makes bots of some difficulty better or worse
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BotSkill {
+    /// Seconds between the bot noticing changes (enemies in sight, incoming bullets, new paths)
+    pub reaction_time: f32,
+    /// The most the bot's aim can be off, in degrees
+    pub aim_error: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BotConfig {
+    pub easy: BotSkill,
+    pub normal: BotSkill,
+    pub hard: BotSkill,
+    /// Bots shoot when they are aiming at most this far from their target, in degrees
+    pub aim_tolerance: f32,
+    /// How hard bots turn towards where they want to go
+    pub steering: f32,
+    /// How close to a waypoint a bot has to be to drive to the next one
+    pub waypoint_radius: f32,
+    /// Bots dodge bullets that would pass closer than this...
+    pub dodge_distance: f32,
+    /// ...in this many seconds
+    pub dodge_time: f32,
+    /// Bots shoot enemies they can't see if a bullet would get to them in this many bounces
+    pub bank_shot_bounces: usize,
+}
+
+impl BotConfig {
+    pub fn skill(&self, difficulty: BotDifficulty) -> &BotSkill {
+        match difficulty {
+            BotDifficulty::Easy => &self.easy,
+            BotDifficulty::Normal => &self.normal,
+            BotDifficulty::Hard => &self.hard,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReplayConfig {
+    /// Record every match
+    pub record: bool,
+    /// Where the last match's replay gets saved, relative to the game's directory
+    pub path: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SpawnConfig {
     pub spawn_time: f32,
@@ -43,6 +203,9 @@ pub struct BeamerConfig {
     pub overheat_time: f32,
     pub self_safety_margin: f32,
     pub shake_magnitude: f32,
+    /// How many times the beam reflects off walls before it stops
+    #[serde(default)]
+    pub reflections: usize,
     pub lock_rotation_when_heating: bool,
     pub lock_movement_when_heating: bool,
     pub lock_rotation_when_shooting: bool,
@@ -52,6 +215,9 @@ pub struct BeamerConfig {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CannonConfig {
     pub shoot_time: f32,
+    /// The most bullets a single tank can have flying at once
+    #[serde(default = "default_max_bullets")]
+    pub max_bullets: usize,
     pub bullet_time: f32,
     pub self_safety_margin: f32,
     pub bullet_density: f32,
@@ -62,11 +228,133 @@ pub struct CannonConfig {
     pub bullet_sprite_num: usize,
 }
 
+/// Configs from before the ammo limit keep the classic limit of 5 bullets
+fn default_max_bullets() -> usize {
+    5
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RocketConfig {
+    pub shoot_time: f32,
+    /// The most rockets a single tank can have flying at once
+    pub max_rockets: usize,
+    /// How long a rocket flies before it gets removed (seconds)
+    pub rocket_time: f32,
+    /// Time after shooting during which the rocket flies straight, before it starts homing (seconds)
+    pub arming_time: f32,
+    pub self_safety_margin: f32,
+    pub rocket_width: f32,
+    pub rocket_height: f32,
+    pub rocket_velocity: f32,
+    /// How fast the rocket can turn (degrees per second)
+    pub turn_speed: f32,
+    /// How close to the middle of a maze cell the rocket has to get before heading to the next one
+    pub waypoint_radius: f32,
+    pub explosion_shake_duration: f32,
+    pub explosion_shake_magnitude: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RailgunConfig {
+    /// Time between pressing fire and the railgun firing (seconds)
+    pub charge_time: f32,
+    pub shoot_time: f32,
+    /// How many times the ray reflects off walls
+    pub reflections: usize,
+    /// How far the ray gets, counting every reflection
+    pub range: f32,
+    pub self_safety_margin: f32,
+    pub beam_width: f32,
+    /// How long the fired ray stays visible (seconds)
+    pub beam_time: f32,
+    pub shake_duration: f32,
+    pub shake_magnitude: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShotgunConfig {
+    /// Reload time between shots (seconds)
+    pub shoot_time: f32,
+    /// How many pellets a single shot fires
+    pub pellet_count: u32,
+    /// The most pellets a single tank can have flying at once, a shot needs room for all of its pellets
+    pub max_pellets: usize,
+    /// The angle between the outermost pellets (degrees)
+    pub spread: f32,
+    /// How long a pellet lives before it gets removed (seconds)
+    pub pellet_time: f32,
+    pub self_safety_margin: f32,
+    pub pellet_density: f32,
+    pub pellet_margin: f32,
+    pub pellet_radius: f32,
+    pub pellet_velocity: f32,
+    pub pellet_restitution: f32,
+}
+
+impl ShotgunConfig {
+    /// Check that a shot fires at least one pellet and that there's room for a whole shot
+    pub fn validate(&self) -> Result<(), String> {
+        if self.pellet_count == 0 {
+            return Err("shotgun: pellet_count has to be at least 1".to_string());
+        }
+        if self.pellet_count as usize > self.max_pellets {
+            return Err(format!(
+                "shotgun: pellet_count {} is more than max_pellets {}, the shotgun could never fire",
+                self.pellet_count, self.max_pellets
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MineConfig {
+    /// Cooldown between dropping mines (seconds)
+    pub shoot_time: f32,
+    /// Time after dropping before a mine can go off (seconds)
+    pub arming_time: f32,
+    /// How long a mine lies before it gets removed (seconds)
+    pub mine_time: f32,
+    /// The most mines a single tank can have laid at once
+    pub max_mines: usize,
+    /// The distance between the tank's back and the dropped mine
+    pub drop_margin: f32,
+    pub mine_radius: f32,
+    /// How close a tank has to get to the edge of a mine to set it off
+    pub trigger_radius: f32,
+    /// Opacity of the mine, so that it's hard to spot
+    pub mine_alpha: f32,
+    pub explosion_shake_duration: f32,
+    pub explosion_shake_magnitude: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FragConfig {
+    pub shoot_time: f32,
+    /// Time after which a bomb bursts on its own (seconds)
+    pub fuse_time: f32,
+    pub self_safety_margin: f32,
+    pub bomb_radius: f32,
+    pub bomb_velocity: f32,
+    pub bomb_density: f32,
+    pub bomb_restitution: f32,
+    /// How many fragments a bomb bursts into, they fly like cannon bullets
+    pub fragment_count: u32,
+    /// How long a fragment lives before it gets removed (seconds)
+    pub fragment_time: f32,
+    pub explosion_shake_duration: f32,
+    pub explosion_shake_magnitude: f32,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PerformanceConfig {
     pub test_wallscan: bool,
     pub wallscan_toi_mod: f32,
     pub dynamic_particles: bool,
+    /// Length of a physics step (seconds)
+    pub physics_timestep: f32,
+    /// The most physics steps simulated in a single frame, the game slows down if it needs more
+    pub max_physics_steps: u32,
 }
 
 
@@ -87,4 +375,34 @@ pub struct DestroyConfig {
 
     pub tank_explosion_shake_duration: f32,
     pub tank_explosion_shake_magnitude: f32,
+}
+
+/// How players score points
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Scoring {
+    /// Tanks still alive when the level resets get a point
+    LastTankStanding,
+    /// A point for every destroyed enemy, minus a point for every time a tank destroys itself.
+    /// Like in LastTankStanding, the round ends when at most one tank is left
+    KillsMinusSuicides,
+    /// Destroyed tanks come back, the round ends when a player destroys this many enemies.
+    /// The score counts won rounds
+    FirstToKills(u32),
+}
+
+impl Scoring {
+    /// Destroyed tanks come back instead of the level getting reset
+    pub fn respawns(self) -> bool {
+        match self {
+            Scoring::FirstToKills(_) => true,
+            Scoring::LastTankStanding | Scoring::KillsMinusSuicides => false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MatchConfig {
+    pub scoring: Scoring,
+    /// Time before a destroyed tank comes back, when tanks respawn (seconds)
+    pub respawn_time: f32,
 }
\ No newline at end of file
@@ -4,101 +4,288 @@ use nalgebra as na;
 
 use amethyst::{
     prelude::*,
-    ecs::{Entities, WriteStorage, WriteExpect},
+    assets::AssetStorage,
+    ecs::{Entities, ReadExpect, WriteStorage, WriteExpect},
     core::Transform,
     core::math as core_na,
     window::ScreenDimensions,
     renderer::resources::Tint,
 };
 
-use crate::utils::mazegen::Maze;
+use crate::utils::mazegen::{Maze, MazeAlgorithm, Cell};
+use crate::utils::levelmap::{LevelMap, LevelMapSet};
 use crate::markers::{DynamicColorMarker, ColorKey};
 use crate::markers::TempMarker;
 use crate::physics;
 use crate::config::MazeConfig;
 use crate::graphics::{ShapeRender, QuadMesh};
+use crate::tank::Team;
+
+/// Everything a level needs to create its entities
+pub type LevelBuildData<'s> = (
+    Entities<'s>,
+    ReadExpect<'s, QuadMesh>,
+    WriteStorage<'s, ShapeRender>,
+    WriteStorage<'s, Tint>,
+    WriteStorage<'s, DynamicColorMarker>,
+    WriteStorage<'s, Transform>,
+    WriteExpect<'s, physics::Physics>,
+    WriteStorage<'s, physics::Body>,
+    WriteStorage<'s, physics::Collider>,
+    WriteStorage<'s, TempMarker>,
+    ReadExpect<'s, ScreenDimensions>,
+);
+
+/// A level the tanks play in. Systems only use the level through this trait
+/// (it's stored in the world as a `Box<dyn Level>`), so any kind of level can be played
+pub trait Level: Send + Sync {
+    /// Create the level's entities for the current round.
+    /// They should all have a `TempMarker`, so that they get removed on the next reset
+    fn rebuild(&mut self, data: &mut LevelBuildData);
+    /// Prepare the next round, called before the level gets rebuilt
+    fn next_round(&mut self);
+    /// Where the players' tanks start a round, in player order.
+    /// There has to be a position for every player that can join (4)
+    fn starting_positions(&self) -> &[na::Point2<f32>];
+    /// Where spawns can appear
+    fn spawn_locations(&self) -> &[na::Point2<f32>];
+    /// Time left until the level gets reset, `None` while the round is still going on
+    fn reset_timer(&self) -> Option<f32>;
+    fn reset_timer_mut(&mut self) -> &mut Option<f32>;
+    /// Points to drive through to get from one position to another without hitting walls,
+    /// ending with the target position. `None` if the target can't be reached
+    fn find_path(&self, from: na::Point2<f32>, to: na::Point2<f32>) -> Option<Vec<na::Point2<f32>>>;
+    /// The middle of the part of the level a position is in, from where paths lead on without hitting walls
+    fn area_center(&self, position: na::Point2<f32>) -> Option<na::Point2<f32>>;
+}
 
+/// A level made of a grid maze, either generated or loaded from a map
 pub struct MazeLevel {
     pub maze: Maze,
-    pub starting_positions: [na::Point2<f32>; 2],
-    pub reset_timer: Option<f32>,
+    maze_config: MazeConfig,
+    /// Maps to play instead of generating mazes, in the order they are played
+    maps: Vec<LevelMap>,
+    starting_positions: Vec<na::Point2<f32>>,
+    spawn_locations: Vec<na::Point2<f32>>,
+    /// Shift of the maze's corner from the world's origin, so that it sits in the middle of the screen
+    shift: na::Vector2<f32>,
+    reset_timer: Option<f32>,
+    /// Number of the current round, used to rotate maze algorithms and maps
+    pub round: usize,
 }
 
 impl MazeLevel {
 
-    pub fn new(world: &mut World, dimensions: &ScreenDimensions) -> Self {
-        let maze_config = world.fetch::<MazeConfig>();
+    pub fn new(world: &mut World) -> Self {
+        let maze_config = (*world.fetch::<MazeConfig>()).clone();
+
+        // Copy the maps we're going to play, so that we don't need the assets later.
+        // Missing maps were already reported when loading, any left are skipped
+        let maps = {
+            let map_storage = world.read_resource::<AssetStorage<LevelMap>>();
+            let level_maps = world.fetch::<LevelMapSet>();
+            maze_config.source.map_names().iter()
+                .filter_map(|name| level_maps.get(name, &map_storage).cloned())
+                .collect()
+        };
 
-        let mut maze = Maze::new(maze_config.maze_width, maze_config.maze_height);
-        maze.build();
+        // The maze gets built in rebuild()
+        let seed = maze_config.seed.unwrap_or_else(Maze::random_seed);
+        let maze = Maze::new(maze_config.maze_width, maze_config.maze_height, seed);
         
         let mut level = MazeLevel {
             maze,
-            starting_positions: [na::Point::origin(); 2],
+            maze_config,
+            maps,
+            starting_positions: Vec::new(),
+            spawn_locations: Vec::new(),
+            shift: na::Vector2::zeros(),
             reset_timer: None,
+            round: 0,
         };
 
         //Actually create wall entities
-        level.rebuild(
-            &maze_config,
-            &world.entities(),
-            &world.fetch::<QuadMesh>(),
-            &mut world.system_data(),
-            &mut world.system_data(),
-            &mut world.system_data(),
-            &mut world.system_data(),
-            &mut world.system_data(),
-            &mut world.system_data(),
-            &mut world.system_data(),
-            &mut world.system_data(),
-            dimensions
-        );
+        level.rebuild(&mut world.system_data());
 
         level
     }
 
-    // This is terrible, perhaps use tuples and a type, just like in a system?
-    // TODO_M: Use a tuple and a type for system data
-    #[allow(clippy::too_many_arguments)]
-    pub fn rebuild(
-        &mut self, 
-        maze_config: &MazeConfig,
-        entities: &Entities, 
-        quad_mesh: &QuadMesh,
-        mut shape_renders: &mut WriteStorage<ShapeRender>,
-        mut tints: &mut WriteStorage<Tint>,
-        mut dyn_color_markers: &mut WriteStorage<DynamicColorMarker>,
-        mut transforms: &mut WriteStorage<Transform>,
-        physics: &mut WriteExpect<physics::Physics>,
-        mut bodies: &mut WriteStorage<physics::Body>,
-        mut colliders: &mut WriteStorage<physics::Collider>,
-        mut temp_markers: &mut WriteStorage<TempMarker>,
-        screen_dimensions: &ScreenDimensions,
-     ) {
+    /// The seed of the current maze, can be put into `MazeConfig` to play the same layout again
+    pub fn seed(&self) -> u64 {
+        self.maze.seed
+    }
+
+    /// The algorithm for this round's maze
+    pub fn algorithm(&self) -> MazeAlgorithm {
+        if self.maze_config.algorithms.is_empty() {
+            MazeAlgorithm::default()
+        } else {
+            self.maze_config.algorithms[self.round % self.maze_config.algorithms.len()]
+        }
+    }
+
+    /// The center of a cell in world coordinates
+    fn cell_position(&self, cell: &Cell, x_shift: f32, y_shift: f32) -> na::Point2<f32> {
+        na::Point2::<f32>::new(
+            cell.col as f32 * self.maze_config.cell_width + (self.maze_config.cell_width * 0.5) + x_shift,
+            cell.row as f32 * self.maze_config.cell_height + (self.maze_config.cell_height * 0.5) + y_shift
+        )
+    }
+
+    /// The cell a point in world coordinates lies in, if it's inside the maze
+    fn cell_at(&self, point: na::Point2<f32>) -> Option<Cell> {
+        let col = ((point.x - self.shift.x) / self.maze_config.cell_width).floor();
+        let row = ((point.y - self.shift.y) / self.maze_config.cell_height).floor();
+        if col < 0.0 || row < 0.0 || col as usize >= self.maze.width || row as usize >= self.maze.height {
+            return None;
+        }
+        Some(Cell::from(col as usize, row as usize))
+    }
+}
+
+impl Level for MazeLevel {
+    fn starting_positions(&self) -> &[na::Point2<f32>] {
+        &self.starting_positions
+    }
+
+    fn spawn_locations(&self) -> &[na::Point2<f32>] {
+        &self.spawn_locations
+    }
+
+    fn reset_timer(&self) -> Option<f32> {
+        self.reset_timer
+    }
+
+    fn reset_timer_mut(&mut self) -> &mut Option<f32> {
+        &mut self.reset_timer
+    }
+
+    fn find_path(&self, from: na::Point2<f32>, to: na::Point2<f32>) -> Option<Vec<na::Point2<f32>>> {
+        let path = self.maze.find_path(&self.cell_at(from)?, &self.cell_at(to)?)?;
+        // Drive through the middle of every cell after the one we're in, the last one is replaced by the target itself
+        let mut points: Vec<na::Point2<f32>> = path.iter()
+            .skip(1)
+            .map(|cell| self.cell_position(cell, self.shift.x, self.shift.y))
+            .collect();
+        points.pop();
+        points.push(to);
+        Some(points)
+    }
+
+    fn area_center(&self, position: na::Point2<f32>) -> Option<na::Point2<f32>> {
+        Some(self.cell_position(&self.cell_at(position)?, self.shift.x, self.shift.y))
+    }
+
+    /// Advance to the next round and pick the seed for its maze. The seed is drawn from
+    /// the current maze's generator, so the whole sequence of mazes is reproducible from the first seed
+    fn next_round(&mut self) {
+        let seed = self.maze.next_seed();
+        self.maze.reseed(seed);
+        self.round += 1;
+    }
+
+    fn rebuild(&mut self, data: &mut LevelBuildData) {
         use np::object::Body;
 
+        let (
+            ref entities,
+            ref quad_mesh,
+            ref mut shape_renders,
+            ref mut tints,
+            ref mut dyn_color_markers,
+            ref mut transforms,
+            ref mut physics,
+            ref mut bodies,
+            ref mut colliders,
+            ref mut temp_markers,
+            ref screen_dimensions,
+        ) = *data;
+
+        // Every wall entity has a TempMarker Component, so it will be removed every level change
+        let map = if self.maps.is_empty() { None } else { Some(&self.maps[self.round % self.maps.len()]) };
+        let mut starting_cells = Vec::new();
+        match map {
+            Some(map) => {
+                // Load a hand-authored maze
+                self.maze.set_layout(map.walls_h.clone(), map.walls_v.clone());
+                self.maze.start_cell = map.starting_cells[0];
+                self.maze.end_cell = map.starting_cells[1];
+                starting_cells = map.starting_cells.clone();
+            },
+            None => {
+                // Reset and regenerate the maze (the same seed always gives the same maze)
+                // A map could have changed the maze's size, so restore it first
+                let algorithm = self.algorithm();
+                self.maze.width = self.maze_config.maze_width;
+                self.maze.height = self.maze_config.maze_height;
+                self.maze.reset();
+                self.maze.build(algorithm);
+                self.maze.braid(self.maze_config.braid_factor);
+                if let Some(max_dead_ends) = self.maze_config.max_dead_ends {
+                    self.maze.limit_dead_ends(max_dead_ends);
+                }
+                log::info!("Built a maze with seed {} using {:?}: {:?}", self.maze.seed, algorithm, self.maze.metrics());
+            }
+        }
+        // Players without a starting cell start in the corners, the first two
+        // in the opposite corners, so that two players are as far from each other as possible
+        let corners = [
+            self.maze.start_cell,
+            self.maze.end_cell,
+            Cell::from(self.maze.width - 1, 0),
+            Cell::from(0, self.maze.height - 1),
+        ];
+        for corner in corners.iter() {
+            if starting_cells.len() >= corners.len() { break; }
+            if !starting_cells.contains(corner) { starting_cells.push(*corner); }
+        }
+        // Corners can repeat in small mazes or already be taken by a map's starting cells,
+        // the remaining players start in the free cells farthest from everyone else
+        while starting_cells.len() < Team::ALL.len() {
+            let distance = |cell: &Cell| starting_cells.iter()
+                .map(|other| (cell.col as isize - other.col as isize).abs() + (cell.row as isize - other.row as isize).abs())
+                .min()
+                .unwrap_or(0);
+            let height = self.maze.height;
+            let farthest = (0..self.maze.width)
+                .flat_map(|col| (0..height).map(move |row| Cell::from(col, row)))
+                .filter(|cell| !starting_cells.contains(cell))
+                .max_by_key(distance);
+            match farthest {
+                Some(cell) => starting_cells.push(cell),
+                // The maze is too small, the config checks make sure there's a cell for every playing tank
+                None => break,
+            }
+        }
+
+        let mut spawn_cells = map.map(|map| map.spawn_cells.clone()).unwrap_or_default();
+        if spawn_cells.is_empty() {
+            // Spawns can appear anywhere except the starting cells
+            for col in 0..self.maze.width {
+                for row in 0..self.maze.height {
+                    let cell = Cell::from(col, row);
+                    if !starting_cells.contains(&cell) {
+                        spawn_cells.push(cell);
+                    }
+                }
+            }
+        }
+
+        let maze_config = &self.maze_config;
+
         //Determine the shift of everything so that the maze sits in the middle of the screen
         //TODO_VL: Scaling, if the maze cannot fit on the screen or is too small
         let x_shift = (screen_dimensions.width() / 2.0) - ((self.maze.width as f32 * maze_config.cell_width) / 2.0);
         let y_shift = (screen_dimensions.height() / 2.0) - ((self.maze.height as f32 * maze_config.cell_height) / 2.0);
-
-        // Every wall entity has a TempMarker Component, so it will be removed every level change
-        // Reset and regenerate the maze
-        self.maze.reset();
-        self.maze.build();
+        self.shift = na::Vector2::new(x_shift, y_shift);
 
         // Determine the starting positions for players
-        // which are the opposite corners of the maze
-        self.starting_positions = [
-            na::Point2::<f32>::new(
-                self.maze.start_cell.col as f32 * maze_config.cell_width + (maze_config.cell_width * 0.5) + x_shift, 
-                self.maze.start_cell.row as f32 * maze_config.cell_height + (maze_config.cell_height) * 0.5 + y_shift
-            ),
-            na::Point2::<f32>::new(
-                self.maze.end_cell.col as f32 * maze_config.cell_width + (maze_config.cell_width * 0.5) + x_shift, 
-                self.maze.end_cell.row as f32 * maze_config.cell_height + (maze_config.cell_height) * 0.5 + y_shift
-            ),
-        ];
+        self.starting_positions = starting_cells.iter()
+            .map(|cell| self.cell_position(cell, x_shift, y_shift))
+            .collect();
+        self.spawn_locations = spawn_cells.iter()
+            .map(|cell| self.cell_position(cell, x_shift, y_shift))
+            .collect();
 
         // Wall position, rigid body, whether the wall is horizontal
         let mut w_pos_rb_h: Vec<(na::Isometry2<f32>, np::object::RigidBody<f32>, bool)> = Vec::new();
@@ -210,13 +397,13 @@ impl MazeLevel {
             // Create the entity
             entities
                 .build_entity()
-                .with(shape_render, &mut shape_renders)
-                .with(Tint(Default::default()), &mut tints)
-                .with(DynamicColorMarker(ColorKey::Walls), &mut dyn_color_markers)
-                .with(wall_transform, &mut transforms)
-                .with(TempMarker(None), &mut temp_markers)
-                .with(wall_body, &mut bodies)
-                .with(wall_collider, &mut colliders)
+                .with(shape_render, shape_renders)
+                .with(Tint(Default::default()), tints)
+                .with(DynamicColorMarker(ColorKey::Walls), dyn_color_markers)
+                .with(wall_transform, transforms)
+                .with(TempMarker(None), temp_markers)
+                .with(wall_body, bodies)
+                .with(wall_collider, colliders)
                 .build();
         }
     }
@@ -24,6 +24,8 @@ mod config;
 mod systems;
 mod markers;
 mod tank;
+mod bot;
+mod replay;
 mod scoreboard;
 mod physics;
 mod weapons;
@@ -43,6 +45,13 @@ fn main() -> amethyst::Result<()> {
     let app_root = application_root_dir()?;
     let resources = app_root.join("res");
 
+    // Play a match without a window, e.g. `cargo run -- --headless --rounds 20`
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--headless") {
+        let options = states::HeadlessOptions::from_args(&args).map_err(amethyst::Error::from_string)?;
+        return run_headless(resources, options);
+    }
+
     let config      = resources.join("config");
     let display_config      = DisplayConfig::load(&config.join( "display.ron"))?;
 
@@ -53,6 +62,7 @@ fn main() -> amethyst::Result<()> {
 
     let game_data = GameDataBuilder::default()
         .with(amethyst::assets::Processor::<crate::utils::color::Colorscheme>::new(), "colorscheme_processor", &[])
+        .with(amethyst::assets::Processor::<crate::utils::levelmap::LevelMap>::new(), "level_map_processor", &[])
         .with_bundle(TransformBundle::new())?
         .with_bundle(input_bundle)?
         .with_bundle(UiBundle::<StringBindings>::new())?
@@ -68,7 +78,16 @@ fn main() -> amethyst::Result<()> {
                 .with_plugin(RenderUi::default())
         )?;
 
-    let game = Application::build(resources, states::LoadingState::default())?
+    // Play a recorded match, e.g. `cargo run -- --replay replays/last.ron`
+    let loading_state = match args.iter().position(|arg| arg == "--replay") {
+        Some(index) => {
+            let path = args.get(index + 1).ok_or_else(|| amethyst::Error::from_string("--replay needs a file"))?;
+            states::LoadingState::with_replay(replay::Replay::read(path).map_err(amethyst::Error::from_string)?)
+        },
+        None => states::LoadingState::default(),
+    };
+
+    let game = Application::build(resources, loading_state)?
         .with_frame_limit(
             FrameRateLimitStrategy::SleepAndYield(Duration::from_millis(2)),
             80
@@ -77,3 +96,16 @@ fn main() -> amethyst::Result<()> {
     game.run_winit_loop(event_loop);
     // Ok(())
 }
+
+/// Run the game without rendering, UI or a window, only the simulation
+fn run_headless(resources: std::path::PathBuf, options: states::HeadlessOptions) -> amethyst::Result<()> {
+    let game_data = GameDataBuilder::default()
+        .with(amethyst::assets::Processor::<crate::utils::levelmap::LevelMap>::new(), "level_map_processor", &[])
+        .with_bundle(TransformBundle::new())?;
+
+    let mut game = Application::build(resources, states::HeadlessState::new(options))?
+        .with_frame_limit(FrameRateLimitStrategy::Unlimited, 0)
+        .build(game_data)?;
+    game.run();
+    Ok(())
+}
@@ -29,6 +29,8 @@ impl From<tank::Team> for ColorKey {
         match t {
             tank::Team::P1 => Self::P1,
             tank::Team::P2 => Self::P2,
+            tank::Team::P3 => Self::P3,
+            tank::Team::P4 => Self::P4,
         }
     }
 }
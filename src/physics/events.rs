@@ -0,0 +1,29 @@
+use amethyst::ecs::Entity;
+use nphysics2d::object::DefaultColliderHandle;
+
+/// Whether colliders started or stopped touching (or overlapping)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollisionKind {
+    Started,
+    Stopped,
+}
+
+/// Two solid colliders started or stopped touching
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollisionEvent {
+    pub kind: CollisionKind,
+    pub entity1: Entity,
+    pub entity2: Entity,
+    pub collider1: DefaultColliderHandle,
+    pub collider2: DefaultColliderHandle,
+}
+
+/// A sensor started or stopped overlapping another collider
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProximityEvent {
+    pub kind: CollisionKind,
+    pub entity1: Entity,
+    pub entity2: Entity,
+    pub collider1: DefaultColliderHandle,
+    pub collider2: DefaultColliderHandle,
+}
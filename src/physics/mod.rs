@@ -5,20 +5,26 @@ use self::np::world::{DefaultMechanicalWorld, DefaultGeometricalWorld};
 use self::np::object::{DefaultBodySet, DefaultColliderSet, DefaultBodyHandle, DefaultColliderHandle};
 use self::np::joint::DefaultJointConstraintSet;
 use self::np::force_generator::DefaultForceGeneratorSet;
-use amethyst::ecs::{Component, DenseVecStorage};
+use ncollide2d::pipeline::narrow_phase::{ContactEvent, ProximityEvent as NcProximityEvent};
+use amethyst::ecs::{Component, DenseVecStorage, FlaggedStorage, Entity, world::Index};
+use std::collections::HashMap;
 
 mod systems;
+mod events;
 pub use systems::*;
+pub use events::*;
+
+// Both storages are flagged, so that the PhysicsEntitySystem knows which entity owns which handle
 
 pub struct Collider { pub handle: DefaultColliderHandle }
-impl Component for Collider { type Storage = DenseVecStorage<Self>; }
+impl Component for Collider { type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>; }
 impl Collider {
     pub fn new(handle: DefaultColliderHandle) -> Self {
         Collider { handle }
     }
 }
 pub struct Body { pub handle: DefaultBodyHandle }
-impl Component for Body { type Storage = DenseVecStorage<Self>; }
+impl Component for Body { type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>; }
 impl Body {
     pub fn new(handle: DefaultBodyHandle) -> Self {
         Body { handle }
@@ -32,6 +38,19 @@ pub struct Physics {
     pub colliders: DefaultColliderSet<f32>,
     _joint_constraints: DefaultJointConstraintSet<f32>,
     _force_generators: DefaultForceGeneratorSet<f32>,
+    /// Time that passed but wasn't simulated yet, less than a timestep after advancing
+    accumulator: f32,
+    /// Positions of moving bodies before the last step, for interpolating between steps
+    previous_positions: HashMap<DefaultBodyHandle, na::Isometry2<f32>>,
+    /// Events of all steps since they were last drained, the geometrical world forgets them every step
+    contact_events: Vec<ContactEvent<DefaultColliderHandle>>,
+    proximity_events: Vec<NcProximityEvent<DefaultColliderHandle>>,
+    /// Entities owning bodies and colliders and the other way around (by entity index,
+    /// because deleted entities are only known by their index), kept up to date by the PhysicsEntitySystem
+    body_entities: HashMap<DefaultBodyHandle, Entity>,
+    entity_bodies: HashMap<Index, DefaultBodyHandle>,
+    collider_entities: HashMap<DefaultColliderHandle, Entity>,
+    entity_colliders: HashMap<Index, DefaultColliderHandle>,
 }
 
 impl Physics {
@@ -45,9 +64,26 @@ impl Physics {
             colliders: DefaultColliderSet::new(),
             _joint_constraints: DefaultJointConstraintSet::new(),
             _force_generators: DefaultForceGeneratorSet::new(),
+            accumulator: 0.0,
+            previous_positions: HashMap::new(),
+            contact_events: Vec::new(),
+            proximity_events: Vec::new(),
+            body_entities: HashMap::new(),
+            entity_bodies: HashMap::new(),
+            collider_entities: HashMap::new(),
+            entity_colliders: HashMap::new(),
         }
     }
 
+    /// Length of a single simulation step (seconds)
+    pub fn timestep(&self) -> f32 {
+        self.mech_world.timestep()
+    }
+
+    pub fn set_timestep(&mut self, timestep: f32) {
+        self.mech_world.set_timestep(timestep);
+    }
+
     pub fn add_rigid_body(&mut self, rigidbody: np::object::RigidBody<f32>) -> DefaultBodyHandle {
         self.bodies.insert(rigidbody)
     }
@@ -112,12 +148,127 @@ impl Physics {
         }
     }
 
+    /// The entity with a `Body` component holding the handle
+    #[allow(dead_code)]
+    pub fn body_entity(&self, handle: DefaultBodyHandle) -> Option<Entity> {
+        self.body_entities.get(&handle).copied()
+    }
+
+    /// The entity with a `Collider` component holding the handle
+    pub fn collider_entity(&self, handle: DefaultColliderHandle) -> Option<Entity> {
+        self.collider_entities.get(&handle).copied()
+    }
+
+    /// The handle in the entity's `Body` component
+    #[allow(dead_code)]
+    pub fn entity_body(&self, entity: Entity) -> Option<DefaultBodyHandle> {
+        self.entity_bodies.get(&entity.id()).copied()
+            .filter(|handle| self.body_entities.get(handle) == Some(&entity))
+    }
+
+    /// The handle in the entity's `Collider` component
+    #[allow(dead_code)]
+    pub fn entity_collider(&self, entity: Entity) -> Option<DefaultColliderHandle> {
+        self.entity_colliders.get(&entity.id()).copied()
+            .filter(|handle| self.collider_entities.get(handle) == Some(&entity))
+    }
+
+    pub(super) fn link_body(&mut self, entity: Entity, handle: DefaultBodyHandle) {
+        self.unlink_body(entity.id());
+        self.body_entities.insert(handle, entity);
+        self.entity_bodies.insert(entity.id(), handle);
+    }
+
+    /// Forget which entity the body belongs to, returns the body's handle
+    pub(super) fn unlink_body(&mut self, index: Index) -> Option<DefaultBodyHandle> {
+        let handle = self.entity_bodies.remove(&index)?;
+        self.body_entities.remove(&handle);
+        Some(handle)
+    }
+
+    pub(super) fn link_collider(&mut self, entity: Entity, handle: DefaultColliderHandle) {
+        self.unlink_collider(entity.id());
+        self.collider_entities.insert(handle, entity);
+        self.entity_colliders.insert(entity.id(), handle);
+    }
+
+    /// Forget which entity the collider belongs to, returns the collider's handle
+    pub(super) fn unlink_collider(&mut self, index: Index) -> Option<DefaultColliderHandle> {
+        let handle = self.entity_colliders.remove(&index)?;
+        self.collider_entities.remove(&handle);
+        Some(handle)
+    }
+
+    /// Number of bodies in the simulation
+    #[allow(dead_code)]
+    pub fn body_count(&self) -> usize {
+        self.bodies.iter().count()
+    }
+
     pub fn maintain(&mut self) {
         self.mech_world.maintain(&mut self.geom_world, &mut self.bodies, &mut self.colliders, &mut self._joint_constraints);
         self.geom_world.maintain(&mut self.bodies, &mut self.colliders);
     }
 
+    /// Simulate the time that passed in as many fixed steps as needed, but at most `max_steps`.
+    /// Time that doesn't fill a whole step is simulated later. Returns the number of steps taken
+    pub fn advance(&mut self, delta: f32, max_steps: u32) -> u32 {
+        self.accumulator += delta;
+        let timestep = self.timestep();
+        let mut steps = 0;
+        while self.accumulator >= timestep {
+            if steps == max_steps {
+                // We can't keep up, slow the game down instead of falling further behind
+                self.accumulator %= timestep;
+                break;
+            }
+            self.step();
+            self.accumulator -= timestep;
+            steps += 1;
+        }
+        steps
+    }
+
+    /// How far the time is between the last step and the next one, 0.0 - 1.0
+    pub fn interpolation(&self) -> f32 {
+        (self.accumulator / self.timestep()).max(0.0).min(1.0)
+    }
+
+    /// A rigid body's position between the last two steps, matching the time that passed since the last step.
+    /// Used to draw bodies moving smoothly, even when there are more frames than steps
+    pub fn interpolated_position(&self, handle: DefaultBodyHandle) -> Option<na::Isometry2<f32>> {
+        let current = self.get_rigid_body(handle)?.position();
+        Some(match self.previous_positions.get(&handle) {
+            Some(previous) => {
+                let t = self.interpolation();
+                na::Isometry2::from_parts(
+                    previous.translation.vector.lerp(&current.translation.vector, t).into(),
+                    previous.rotation.slerp(&current.rotation, t),
+                )
+            },
+            None => *current,
+        })
+    }
+
+    /// Move a rigid body somewhere without it being drawn moving there
+    pub fn teleport_rigid_body(&mut self, handle: DefaultBodyHandle, position: na::Isometry2<f32>) {
+        if let Some(rb) = self.get_rigid_body_mut(handle) {
+            rb.set_position(position);
+        }
+        self.previous_positions.remove(&handle);
+    }
+
+    /// Simulate a single step
     pub fn step(&mut self) {
+        use self::np::object::Body;
+        self.previous_positions.clear();
+        for (handle, body) in self.bodies.iter() {
+            if let Some(rb) = body.downcast_ref::<np::object::RigidBody<f32>>() {
+                if rb.status() != np::object::BodyStatus::Static {
+                    self.previous_positions.insert(handle, *rb.position());
+                }
+            }
+        }
         self.mech_world.step(
             &mut self.geom_world,
             &mut self.bodies,
@@ -125,5 +276,17 @@ impl Physics {
             &mut self._joint_constraints,
             &mut self._force_generators
         );
+        self.contact_events.extend(self.geom_world.contact_events().iter().cloned());
+        self.proximity_events.extend(self.geom_world.proximity_events().iter().cloned());
+    }
+
+    /// Take the contact events of the steps since the last call
+    pub fn drain_contact_events(&mut self) -> std::vec::Drain<'_, ContactEvent<DefaultColliderHandle>> {
+        self.contact_events.drain(..)
+    }
+
+    /// Take the proximity events of the steps since the last call
+    pub fn drain_proximity_events(&mut self) -> std::vec::Drain<'_, NcProximityEvent<DefaultColliderHandle>> {
+        self.proximity_events.drain(..)
     }
 }
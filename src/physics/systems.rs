@@ -1,26 +1,150 @@
+use ncollide2d::pipeline::narrow_phase::ContactEvent;
+use ncollide2d::query::Proximity;
 use amethyst::{
     core::Transform,
+    core::timing::Time,
     ecs::{
-        System, Join,
-        ReadStorage, WriteStorage, WriteExpect, ReadExpect
-    }
+        System, SystemData, World, WorldExt, Join, Entities,
+        Read, Write, ReadStorage, WriteStorage, WriteExpect, ReadExpect,
+        storage::ComponentEvent,
+    },
+    shrev::{EventChannel, ReaderId},
 };
 
-use crate::physics::{Physics, Body};
+use crate::physics::{Physics, Body, Collider, CollisionEvent, ProximityEvent, CollisionKind};
+use crate::config::PerformanceConfig;
 
+/// Steps the physics with a fixed timestep, as many times as the time that passed requires,
+/// so that the game runs at the same speed no matter the frame rate
 pub struct StepperSystem;
 
 impl<'s> System<'s> for StepperSystem {
     type SystemData = (
         WriteExpect<'s, Physics>,
+        Read<'s, Time>,
+        ReadExpect<'s, PerformanceConfig>,
     );
     
-    fn run (&mut self, mut physics: Self::SystemData) {
-        physics.0.step();
+    fn run (&mut self, (mut physics, time, performance_config): Self::SystemData) {
+        physics.advance(time.delta_seconds(), performance_config.max_physics_steps);
+    }
+}
+
+/// Keeps track of which entity owns which body and collider handle, so that `Physics` can look them up.
+/// Reacts to `Body` and `Collider` components being inserted, replaced or removed.
+/// When they are removed (for example by deleting their entity), the body or collider gets removed from the simulation too,
+/// so systems only have to delete entities.
+///
+/// Deleted entities lose their components only at the end of the frame, so this has to run before stepping the physics
+/// in the next frame, otherwise deleted things (like walls of the previous level) would still be simulated for a step
+#[derive(Default)]
+pub struct PhysicsEntitySystem {
+    body_reader: Option<ReaderId<ComponentEvent>>,
+    collider_reader: Option<ReaderId<ComponentEvent>>,
+}
+
+impl<'s> System<'s> for PhysicsEntitySystem {
+    type SystemData = (
+        Entities<'s>,
+        ReadStorage<'s, Body>,
+        ReadStorage<'s, Collider>,
+        WriteExpect<'s, Physics>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.body_reader = Some(world.write_storage::<Body>().register_reader());
+        self.collider_reader = Some(world.write_storage::<Collider>().register_reader());
+    }
+
+    fn run (&mut self, (entities, bodies, colliders, mut physics): Self::SystemData) {
+        for event in bodies.channel().read(self.body_reader.as_mut().unwrap()) {
+            match event {
+                ComponentEvent::Inserted(index) | ComponentEvent::Modified(index) => {
+                    let entity = entities.entity(*index);
+                    // The entity might have lost the component since
+                    if let Some(body) = bodies.get(entity) {
+                        physics.link_body(entity, body.handle);
+                    }
+                },
+                ComponentEvent::Removed(index) => {
+                    if let Some(handle) = physics.unlink_body(*index) {
+                        // Colliders attached to the body get removed with it
+                        physics.remove_rigid_body(handle);
+                    }
+                },
+            }
+        }
+        for event in colliders.channel().read(self.collider_reader.as_mut().unwrap()) {
+            match event {
+                ComponentEvent::Inserted(index) | ComponentEvent::Modified(index) => {
+                    let entity = entities.entity(*index);
+                    if let Some(collider) = colliders.get(entity) {
+                        physics.link_collider(entity, collider.handle);
+                    }
+                },
+                ComponentEvent::Removed(index) => {
+                    if let Some(handle) = physics.unlink_collider(*index) {
+                        physics.remove_collider(handle);
+                    }
+                },
+            }
+        }
+    }
+}
+
+/// Publishes the contacts and proximities of the steps taken this frame as `CollisionEvent`s and `ProximityEvent`s,
+/// so that systems can react to them without asking the physics about every collider they care about
+pub struct CollisionEventSystem;
+
+impl<'s> System<'s> for CollisionEventSystem {
+    type SystemData = (
+        WriteExpect<'s, Physics>,
+        Write<'s, EventChannel<CollisionEvent>>,
+        Write<'s, EventChannel<ProximityEvent>>,
+    );
+
+    fn run (&mut self, (mut physics, mut collision_events, mut proximity_events): Self::SystemData) {
+        let physics = &mut *physics;
+        let contact_events: Vec<_> = physics.drain_contact_events().collect();
+        let nc_proximity_events: Vec<_> = physics.drain_proximity_events().collect();
+
+        // Colliders that were removed since don't belong to any entity, so their events get dropped
+        collision_events.iter_write(contact_events.into_iter().filter_map(|event| {
+            let (kind, collider1, collider2) = match event {
+                ContactEvent::Started(collider1, collider2) => (CollisionKind::Started, collider1, collider2),
+                ContactEvent::Stopped(collider1, collider2) => (CollisionKind::Stopped, collider1, collider2),
+            };
+            Some(CollisionEvent {
+                kind,
+                entity1: physics.collider_entity(collider1)?,
+                entity2: physics.collider_entity(collider2)?,
+                collider1,
+                collider2,
+            })
+        }));
+
+        proximity_events.iter_write(nc_proximity_events.into_iter().filter_map(|event| {
+            // Being within the margin doesn't count, the colliders have to overlap
+            let kind = match (event.prev_status, event.new_status) {
+                (Proximity::Intersecting, Proximity::Intersecting) => return None,
+                (_, Proximity::Intersecting) => CollisionKind::Started,
+                (Proximity::Intersecting, _) => CollisionKind::Stopped,
+                _ => return None,
+            };
+            Some(ProximityEvent {
+                kind,
+                entity1: physics.collider_entity(event.collider1)?,
+                entity2: physics.collider_entity(event.collider2)?,
+                collider1: event.collider1,
+                collider2: event.collider2,
+            })
+        }));
     }
 }
 
 ///Physics To Transform System
+///Transforms are interpolated between the last two physics steps
 pub struct PTTSystem;
 
 impl<'s> System<'s> for PTTSystem {
@@ -32,8 +156,7 @@ impl<'s> System<'s> for PTTSystem {
     
     fn run (&mut self, (physics, bodies, mut transforms): Self::SystemData) {
         for (body, transform) in (&bodies, &mut transforms).join() {
-            if let Some(rb) = physics.get_rigid_body(body.handle) {
-                let pos = rb.position();
+            if let Some(pos) = physics.interpolated_position(body.handle) {
                 transform.set_translation(
                     amethyst::core::math::Vector3::<f32>::new(
                         pos.translation.vector.x, pos.translation.vector.y, transform.translation().z
@@ -48,4 +171,52 @@ impl<'s> System<'s> for PTTSystem {
             }
         }
     }
-}
\ No newline at end of file
+}
+#[test]
+/// Entities can be found by their handles until they get deleted, then their physics objects get removed
+fn test_physics_entities() {
+    use amethyst::ecs::{Builder, RunNow};
+    use nphysics2d as np;
+    use ncollide2d as nc;
+
+    let mut world = World::new();
+    world.insert(Physics::new());
+    let mut system = PhysicsEntitySystem::default();
+    System::setup(&mut system, &mut world);
+
+    let (body, collider) = {
+        let mut physics = world.write_resource::<Physics>();
+        let body = physics.add_rigid_body(np::object::RigidBodyDesc::new().build());
+        let collider = physics.add_collider(
+            np::object::ColliderDesc::new(nc::shape::ShapeHandle::new(nc::shape::Ball::new(1.0)))
+                .build(np::object::BodyPartHandle(body, 0))
+        );
+        (body, collider)
+    };
+    let entity = world.create_entity()
+        .with(Body::new(body))
+        .with(Collider::new(collider))
+        .build();
+    system.run_now(&world);
+    {
+        let physics = world.read_resource::<Physics>();
+        assert_eq!(physics.body_entity(body), Some(entity));
+        assert_eq!(physics.collider_entity(collider), Some(entity));
+        assert_eq!(physics.entity_body(entity), Some(body));
+        assert_eq!(physics.entity_collider(entity), Some(collider));
+        assert_eq!(physics.body_count(), 1);
+    }
+
+    world.delete_entity(entity).unwrap();
+    world.maintain();
+    system.run_now(&world);
+    let physics = world.read_resource::<Physics>();
+    assert_eq!(physics.body_entity(body), None);
+    assert_eq!(physics.collider_entity(collider), None);
+    assert_eq!(physics.entity_body(entity), None);
+    // Deleting the entity removed its body and collider from the simulation
+    assert!(physics.get_body(body).is_none());
+    assert!(physics.get_collider(collider).is_none());
+    // Every body belonged to an entity, so none leaked
+    assert_eq!(physics.body_count(), 0);
+}
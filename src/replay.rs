@@ -0,0 +1,243 @@
+use serde::{Serialize, Deserialize};
+use rand::{SeedableRng, rngs::StdRng};
+use amethyst::prelude::*;
+use crate::config;
+use crate::tank::Team;
+use crate::physics::Physics;
+
+/// Replays saved with a different version can't be played
+pub const REPLAY_VERSION: u32 = 9;
+
+/// Random number generator for everything random that changes how a match plays out (like spawns or debris).
+/// It's seeded, so that a replay with the same seed plays out the same way
+pub struct GameRng(pub StdRng);
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        GameRng(StdRng::seed_from_u64(seed))
+    }
+}
+
+/// A tank's controls in a single frame
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct TankInput {
+    pub forward: f32,
+    pub side: f32,
+    pub fire: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ReplayFrame {
+    /// Length of the frame (seconds)
+    pub delta: f32,
+    /// Controls of every tank, in player order
+    pub inputs: Vec<TankInput>,
+    /// The level was reset by hand before this frame
+    #[serde(default)]
+    pub reset_level: bool,
+}
+
+/// Every config that changes how a match plays out
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReplayConfigs {
+    pub tank: config::TankConfig,
+    /// Always has a seed, so that the same mazes get generated
+    pub maze: config::MazeConfig,
+    pub beamer: config::BeamerConfig,
+    pub cannon: config::CannonConfig,
+    pub rocket: config::RocketConfig,
+    pub railgun: config::RailgunConfig,
+    pub shotgun: config::ShotgunConfig,
+    pub mine: config::MineConfig,
+    pub frag: config::FragConfig,
+    pub spawn: config::SpawnConfig,
+    pub destroy: config::DestroyConfig,
+    pub match_config: config::MatchConfig,
+    pub players: config::PlayersConfig,
+    pub performance: config::PerformanceConfig,
+    pub bot: config::BotConfig,
+}
+
+impl ReplayConfigs {
+    /// Copy the configs the game uses right now
+    pub fn from_world(world: &World) -> Self {
+        ReplayConfigs {
+            tank: (*world.read_resource::<config::TankConfig>()).clone(),
+            maze: (*world.read_resource::<config::MazeConfig>()).clone(),
+            beamer: (*world.read_resource::<config::BeamerConfig>()).clone(),
+            cannon: (*world.read_resource::<config::CannonConfig>()).clone(),
+            rocket: (*world.read_resource::<config::RocketConfig>()).clone(),
+            railgun: (*world.read_resource::<config::RailgunConfig>()).clone(),
+            shotgun: (*world.read_resource::<config::ShotgunConfig>()).clone(),
+            mine: (*world.read_resource::<config::MineConfig>()).clone(),
+            frag: (*world.read_resource::<config::FragConfig>()).clone(),
+            spawn: (*world.read_resource::<config::SpawnConfig>()).clone(),
+            destroy: (*world.read_resource::<config::DestroyConfig>()).clone(),
+            match_config: (*world.read_resource::<config::MatchConfig>()).clone(),
+            players: (*world.read_resource::<config::PlayersConfig>()).clone(),
+            performance: (*world.read_resource::<config::PerformanceConfig>()).clone(),
+            bot: (*world.read_resource::<config::BotConfig>()).clone(),
+        }
+    }
+
+    /// Replace the configs the game uses with these
+    pub fn insert_into(self, world: &mut World) {
+        world.write_resource::<Physics>().set_timestep(self.performance.physics_timestep);
+        world.insert(self.tank);
+        world.insert(self.maze);
+        world.insert(self.beamer);
+        world.insert(self.cannon);
+        world.insert(self.rocket);
+        world.insert(self.railgun);
+        world.insert(self.shotgun);
+        world.insert(self.mine);
+        world.insert(self.frag);
+        world.insert(self.spawn);
+        world.insert(self.destroy);
+        world.insert(self.match_config);
+        world.insert(self.players);
+        world.insert(self.performance);
+        world.insert(self.bot);
+    }
+}
+
+/// A recorded match: everything needed to play it again exactly the same way
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Replay {
+    pub version: u32,
+    /// Seed of the `GameRng`
+    pub seed: u64,
+    pub configs: ReplayConfigs,
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    pub fn new(seed: u64, configs: ReplayConfigs) -> Self {
+        Replay {
+            version: REPLAY_VERSION,
+            seed,
+            configs,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Read a replay from a file, fails if it was saved by a different version
+    pub fn read<P: AsRef<std::path::Path>>(path: P) -> Result<Self, String> {
+        let replay = Replay::load(path.as_ref())
+            .map_err(|error| format!("Couldn't read replay {}: {}", path.as_ref().display(), error))?;
+        if replay.version != REPLAY_VERSION {
+            return Err(format!("Replay version {} can't be played, only version {}", replay.version, REPLAY_VERSION));
+        }
+        // The replay's configs replace the checked ones
+        replay.configs.shotgun.validate()?;
+        Ok(replay)
+    }
+
+    /// Write the replay to a file, creating its directory if needed
+    pub fn save<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), String> {
+        if let Some(directory) = path.as_ref().parent() {
+            std::fs::create_dir_all(directory).map_err(|error| error.to_string())?;
+        }
+        self.write(path.as_ref()).map_err(|error| error.to_string())
+    }
+}
+
+/// A resource recording the match while it's being played
+pub struct ReplayRecorder {
+    pub replay: Replay,
+    /// The level was reset by hand since the last recorded frame
+    pub reset_level: bool,
+}
+
+impl ReplayRecorder {
+    pub fn new(replay: Replay) -> Self {
+        ReplayRecorder { replay, reset_level: false }
+    }
+
+    /// Record the controls of every tank in the frame that just passed
+    pub fn record(&mut self, delta: f32, inputs: Vec<TankInput>) {
+        self.replay.frames.push(ReplayFrame {
+            delta,
+            inputs,
+            reset_level: self.reset_level,
+        });
+        self.reset_level = false;
+    }
+}
+
+/// A resource playing a replay back, frame by frame
+pub struct ReplayPlayer {
+    replay: Replay,
+    frame: usize,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        ReplayPlayer { replay, frame: 0 }
+    }
+
+    /// The frame that's being played, `None` when the replay is over
+    pub fn current(&self) -> Option<&ReplayFrame> {
+        self.replay.frames.get(self.frame)
+    }
+
+    /// A tank's recorded controls in the current frame
+    pub fn input(&self, team: Team) -> TankInput {
+        self.current()
+            .and_then(|frame| frame.inputs.get(team as usize))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn next_frame(&mut self) {
+        self.frame += 1;
+    }
+}
+
+#[test]
+/// Replays are written and read back the same, but only by the same version
+fn test_replay_file() {
+    let configs = ReplayConfigs {
+        tank: config::TankConfig::load("res/config/tank.ron").unwrap(),
+        maze: config::MazeConfig::load("res/config/maze.ron").unwrap(),
+        beamer: config::BeamerConfig::load("res/config/beamer.ron").unwrap(),
+        cannon: config::CannonConfig::load("res/config/cannon.ron").unwrap(),
+        rocket: config::RocketConfig::load("res/config/rocket.ron").unwrap(),
+        railgun: config::RailgunConfig::load("res/config/railgun.ron").unwrap(),
+        shotgun: config::ShotgunConfig::load("res/config/shotgun.ron").unwrap(),
+        mine: config::MineConfig::load("res/config/mine.ron").unwrap(),
+        frag: config::FragConfig::load("res/config/frag.ron").unwrap(),
+        spawn: config::SpawnConfig::load("res/config/spawn.ron").unwrap(),
+        destroy: config::DestroyConfig::load("res/config/destroy.ron").unwrap(),
+        match_config: config::MatchConfig::load("res/config/match.ron").unwrap(),
+        players: config::PlayersConfig::load("res/config/players.ron").unwrap(),
+        performance: config::PerformanceConfig::load("res/config/performance.ron").unwrap(),
+        bot: config::BotConfig::load("res/config/bot.ron").unwrap(),
+    };
+    let mut recorder = ReplayRecorder::new(Replay::new(42, configs));
+    recorder.record(0.0125, vec![TankInput { forward: 1.0, side: 0.0, fire: true }, TankInput::default()]);
+    recorder.reset_level = true;
+    recorder.record(0.0125, vec![TankInput::default(), TankInput { forward: 0.0, side: -1.0, fire: false }]);
+
+    let path = std::env::temp_dir().join("tanks_test_replay.ron");
+    recorder.replay.save(&path).unwrap();
+    let read = Replay::read(&path).unwrap();
+    assert_eq!(read.seed, 42);
+    assert_eq!(read.frames, recorder.replay.frames);
+    assert!(!read.frames[0].reset_level && read.frames[1].reset_level);
+
+    let mut player = ReplayPlayer::new(read);
+    assert!(player.input(Team::P1).fire);
+    player.next_frame();
+    assert_eq!(player.input(Team::P2).side, -1.0);
+    // Tanks without recorded input stand still
+    assert_eq!(player.input(Team::P3), TankInput::default());
+    player.next_frame();
+    assert!(player.current().is_none());
+
+    let mut old = recorder.replay.clone();
+    old.version = REPLAY_VERSION + 1;
+    old.save(&path).unwrap();
+    assert!(Replay::read(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
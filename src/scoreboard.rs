@@ -1,52 +1,179 @@
 use crate::tank::Team;
+use crate::config::Scoring;
 use amethyst::ecs::{Entity, WriteStorage};
 use amethyst::ui::UiText;
 
+/// What a team did during the whole game
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TeamStats {
+    /// Enemies the team destroyed
+    pub kills: u32,
+    /// Times the team's tank got destroyed, including suicides
+    pub deaths: u32,
+    /// Times the team's tank destroyed itself
+    pub suicides: u32,
+    /// Rounds the team won
+    pub wins: u32,
+}
+
 /// Scoreboard resource that systems can use to read or write to the score counter
 pub struct Scoreboard {
-    scores: Vec<u32>,
+    scoring: Scoring,
+    scores: Vec<i32>,
+    /// Kills in the current round, when playing first to some kills
+    round_kills: Vec<u32>,
+    stats: Vec<TeamStats>,
+    teams: Vec<Team>,
     alive: Vec<Team>,
+    rounds: u32,
+    /// Score counters shown on screen, in team order. There are none when running headless
     pub texts: Vec<Entity>,
 }
 
 impl Scoreboard {
-    /// Creates a new Scoreboard; By default every team's score is 0
-
-    /// Because we can't iter over enums in Rust, we can't determine
-    /// the length of the Team enum, so this Vec declaration has to 
-    /// be changed everytime we add a team or remove one. Oof.
-    pub fn new() -> Self {
+    /// Creates a new Scoreboard for the first `player_count` teams; By default every team's score is 0
+    pub fn new(player_count: usize, scoring: Scoring) -> Self {
+        let teams = Team::ALL[..player_count].to_vec();
         Scoreboard {
-            scores: vec![0, 0],
-            alive: vec![Team::P1, Team::P2],
+            scoring,
+            scores: vec![0; player_count],
+            round_kills: vec![0; player_count],
+            stats: vec![TeamStats::default(); player_count],
+            alive: teams.clone(),
+            teams,
+            rounds: 0,
             texts: vec![]
         }
     }
-    /// Report that the tank was destroyed so we can determine the winner later
-    pub fn report_destroyed(&mut self, team: Team) {
-        self.alive.retain(|t| *t != team);
-    }
-    
-    /// Check who is still alive (only one tank should be) and update it's score
-    pub fn update_winners(&mut self, ui_text: &mut WriteStorage<UiText>) {
-        // We could drain(..) here instead of clear() following iter(),
-        // but we need access to other self fields so it's not possible
-        for winner in self.alive.iter() {
-            self.scores[*winner as usize] += 1;
-            ui_text.get_mut(self.get_text(*winner)).unwrap()
-                .text = self.get_score(*winner).to_string();
+
+    /// Report that the tank was destroyed, by the `killer` team if we know who shot it
+    pub fn report_destroyed(&mut self, victim: Team, killer: Option<Team>) {
+        self.alive.retain(|t| *t != victim);
+        self.stats[victim as usize].deaths += 1;
+        match killer {
+            Some(killer) if killer == victim => {
+                self.stats[victim as usize].suicides += 1;
+                if self.scoring == Scoring::KillsMinusSuicides {
+                    self.scores[victim as usize] -= 1;
+                }
+            },
+            Some(killer) => {
+                self.stats[killer as usize].kills += 1;
+                match self.scoring {
+                    Scoring::KillsMinusSuicides => self.scores[killer as usize] += 1,
+                    Scoring::FirstToKills(_) => self.round_kills[killer as usize] += 1,
+                    Scoring::LastTankStanding => (),
+                }
+            },
+            None => (),
         }
+    }
+
+    /// The team that has enough kills to end the round, when playing first to some kills
+    pub fn round_winner(&self) -> Option<Team> {
+        match self.scoring {
+            Scoring::FirstToKills(kills) => self.teams.iter()
+                .copied()
+                .find(|team| self.round_kills[*team as usize] >= kills),
+            Scoring::LastTankStanding | Scoring::KillsMinusSuicides => None,
+        }
+    }
+
+    /// End the round: give points to whoever won it and get ready for the next one
+    pub fn update_winners(&mut self) {
+        match self.scoring {
+            // Check who is still alive (only one tank should be) and update it's score
+            Scoring::LastTankStanding => {
+                for winner in self.alive.iter() {
+                    self.scores[*winner as usize] += 1;
+                    self.stats[*winner as usize].wins += 1;
+                }
+            },
+            Scoring::KillsMinusSuicides => (),
+            // The score counts won rounds, and every round is a new race to the kills
+            Scoring::FirstToKills(_) => {
+                if let Some(winner) = self.round_winner() {
+                    self.scores[winner as usize] += 1;
+                    self.stats[winner as usize].wins += 1;
+                }
+                for kills in self.round_kills.iter_mut() {
+                    *kills = 0;
+                }
+            },
+        }
+        self.rounds += 1;
         self.alive.clear();
-        self.alive.push(Team::P1);
-        self.alive.push(Team::P2);
+        self.alive.extend_from_slice(&self.teams);
+    }
+
+    /// Show the current scores in the score counters
+    pub fn update_texts(&self, ui_text: &mut WriteStorage<UiText>) {
+        for (team, text) in self.teams.iter().zip(self.texts.iter()) {
+            if let Some(text) = ui_text.get_mut(*text) {
+                text.text = self.get_score(*team).to_string();
+            }
+        }
     }
 
     /// Reads a score for a team
-    pub fn get_score(&self, team: Team) -> u32 {
+    pub fn get_score(&self, team: Team) -> i32 {
         self.scores[team as usize]
     }
-    /// Returns an entity of a team's UI counter's text
-    pub fn get_text(&self, team: Team) -> Entity {
-        self.texts[team as usize]
+    /// Reads what a team did during the game
+    pub fn get_stats(&self, team: Team) -> TeamStats {
+        self.stats[team as usize]
+    }
+    /// Number of rounds that were played to the end
+    pub fn rounds(&self) -> u32 {
+        self.rounds
+    }
+    /// Teams playing the game
+    pub fn teams(&self) -> &[Team] {
+        &self.teams
+    }
+}
+
+#[test]
+/// Kills and suicides change the score depending on the scoring
+fn test_scoring() {
+    let mut last_standing = Scoreboard::new(2, Scoring::LastTankStanding);
+    last_standing.report_destroyed(Team::P2, Some(Team::P1));
+    assert_eq!(last_standing.get_score(Team::P1), 0);
+    last_standing.update_winners();
+    assert_eq!(last_standing.get_score(Team::P1), 1);
+    assert_eq!(last_standing.get_score(Team::P2), 0);
+
+    let mut kills = Scoreboard::new(2, Scoring::KillsMinusSuicides);
+    kills.report_destroyed(Team::P2, Some(Team::P1));
+    kills.report_destroyed(Team::P1, Some(Team::P1));
+    kills.report_destroyed(Team::P1, Some(Team::P1));
+    assert_eq!(kills.get_score(Team::P1), -1);
+    assert_eq!(kills.get_stats(Team::P1), TeamStats { kills: 1, deaths: 2, suicides: 2, wins: 0 });
+    assert_eq!(kills.get_stats(Team::P2), TeamStats { kills: 0, deaths: 1, suicides: 0, wins: 0 });
+
+    let mut first_to = Scoreboard::new(2, Scoring::FirstToKills(2));
+    first_to.report_destroyed(Team::P2, Some(Team::P1));
+    assert_eq!(first_to.round_winner(), None);
+    first_to.report_destroyed(Team::P2, Some(Team::P1));
+    assert_eq!(first_to.round_winner(), Some(Team::P1));
+    assert_eq!(first_to.get_score(Team::P1), 0);
+    first_to.update_winners();
+    assert_eq!(first_to.get_score(Team::P1), 1);
+    assert_eq!(first_to.get_stats(Team::P1).wins, 1);
+    assert_eq!(first_to.round_winner(), None);
+}
+
+#[test]
+/// With three players the round goes on after the first death, only the last tank standing scores
+fn test_three_players() {
+    let mut scoreboard = Scoreboard::new(3, Scoring::LastTankStanding);
+    scoreboard.report_destroyed(Team::P2, Some(Team::P1));
+    for team in [Team::P1, Team::P2, Team::P3].iter() {
+        assert_eq!(scoreboard.get_score(*team), 0);
     }
+    scoreboard.report_destroyed(Team::P1, Some(Team::P3));
+    scoreboard.update_winners();
+    assert_eq!(scoreboard.get_score(Team::P1), 0);
+    assert_eq!(scoreboard.get_score(Team::P2), 0);
+    assert_eq!(scoreboard.get_score(Team::P3), 1);
 }
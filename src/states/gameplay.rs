@@ -4,6 +4,8 @@ use nalgebra as na;
 use amethyst::{
     assets::Loader,
     core::transform::Transform,
+    core::timing::Time,
+    utils::application_dir,
     input::{is_close_requested, is_key_down, VirtualKeyCode, get_key, ElementState},
     prelude::*,
     renderer::{
@@ -11,7 +13,6 @@ use amethyst::{
 
         SpriteRender
     },
-    window::ScreenDimensions,
     ui::{Anchor, TtfFormat, UiText, UiTransform},
     ecs::{Dispatcher, DispatcherBuilder},
     core::ArcThreadPool,
@@ -19,11 +20,14 @@ use amethyst::{
 use crate::graphics::TintBox;
 use crate::markers::{DynamicColorMarker, ColorKey};
 use crate::utils::TanksSpriteSheet;
-use crate::level::MazeLevel;
-use crate::config::TankConfig;
+use crate::level::{Level, MazeLevel};
+use crate::config::{TankConfig, MazeConfig, PlayersConfig, BotConfig, ReplayConfig, MatchConfig};
+use crate::replay::{Replay, ReplayConfigs, ReplayRecorder, ReplayPlayer, GameRng};
+use crate::utils::mazegen::Maze;
+use crate::bot::Bot;
 use crate::tank::{Tank, Team};
 use crate::scoreboard::Scoreboard;
-use crate::weapons::Weapon;
+use crate::weapons::CannonState;
 
 use crate::physics;
 use crate::systems;
@@ -31,42 +35,50 @@ use crate::systems;
 #[derive(Default)]
 pub struct GameplayState<'a, 'b> {
     dispatcher: Option<Dispatcher<'a, 'b>>,
+    /// A replay to play instead of letting players control the tanks
+    replay: Option<Replay>,
+}
+
+impl<'a, 'b> GameplayState<'a, 'b> {
+    pub fn with_replay(replay: Replay) -> Self {
+        GameplayState {
+            dispatcher: None,
+            replay: Some(replay),
+        }
+    }
 }
 
 impl<'a, 'b> SimpleState for GameplayState<'a, 'b> {
     fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
         let world = data.world;
-        let mut dispatcher = 
-            DispatcherBuilder::new()
-                .with(systems::LevelSystem, "level_system", &[])
-                .with(systems::TankSystem, "tank_system", &[/*"input_system",*/ "level_system"])
-                .with(systems::SpawnSystem::default(), "spawn_system", &["level_system"])
-
-                .with_barrier()
-                .with(systems::BeamerSystem, "beamer_system", &[])
-                .with(systems::CannonSystem, "cannon_system", &[])
-                // .with(systems::RocketSystem, "rocket_system", &["spawn_system"])
-
-                .with_barrier()
-                .with(systems::DestroySystem, "destroy_system", &[])
-                .with(systems::CameraShakeSystem, "shake_system", &["destroy_system"])
 
-                .with_barrier()
-                .with(physics::StepperSystem, "stepper_system", &[])
-                .with(physics::PTTSystem, "physics_to_transform_system", &["stepper_system"])
-
-                .with_barrier()
-                .with(systems::ColorSystem, "color_system", &[])
-
-                .with_pool((*world.read_resource::<ArcThreadPool>()).clone())
-                .build();
-
-        dispatcher.setup(world);
-        self.dispatcher = Some(dispatcher);
+        let seed = match self.replay.take() {
+            Some(replay) => {
+                // Play with the same configs, seeds and controls as the recorded match
+                let seed = replay.seed;
+                replay.configs.clone().insert_into(world);
+                world.insert(ReplayPlayer::new(replay));
+                seed
+            },
+            None => {
+                // Pick the maze seed now, so that it gets recorded
+                let mut maze_config = world.write_resource::<MazeConfig>();
+                maze_config.seed = Some(maze_config.seed.unwrap_or_else(Maze::random_seed));
+                drop(maze_config);
+
+                let seed = Maze::random_seed();
+                if world.read_resource::<ReplayConfig>().record {
+                    let recorder = ReplayRecorder::new(Replay::new(seed, ReplayConfigs::from_world(world)));
+                    world.insert(recorder);
+                }
+                seed
+            },
+        };
+        world.insert(GameRng::new(seed));
+        self.dispatcher = Some(build_dispatcher(world, false));
 
-        let dimensions = (*world.fetch::<ScreenDimensions>()).clone();
         // Initialize the level
-        init_level(world, &dimensions);
+        init_level(world);
         // Initialize players
         init_players(world);
         // Initialize the scoreboard
@@ -74,12 +86,46 @@ impl<'a, 'b> SimpleState for GameplayState<'a, 'b> {
     }
 
     fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        // Replays play every frame with its recorded length, so that the physics get stepped the same way
+        if let Some(replay_player) = data.world.try_fetch::<ReplayPlayer>() {
+            let frame = match replay_player.current() {
+                Some(frame) => frame,
+                None => {
+                    log::info!("Replay finished");
+                    return Trans::Quit;
+                },
+            };
+            data.world.write_resource::<Time>().set_delta_seconds(frame.delta);
+            if frame.reset_level {
+                data.world.write_resource::<Box<dyn Level>>()
+                    .reset_timer_mut().replace(0.1);
+            }
+        }
         if let Some(dispatcher) = self.dispatcher.as_mut() {
             dispatcher.dispatch(&data.world);
         }
+        if let Some(mut replay_player) = data.world.try_fetch_mut::<ReplayPlayer>() {
+            replay_player.next_frame();
+        }
         Trans::None
     }
 
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        if let Some(recorder) = data.world.try_fetch::<ReplayRecorder>() {
+            let path = match application_dir(&data.world.read_resource::<ReplayConfig>().path) {
+                Ok(path) => path,
+                Err(error) => {
+                    log::error!("Couldn't save the replay: {}", error);
+                    return;
+                },
+            };
+            match recorder.replay.save(&path) {
+                Ok(()) => log::info!("Saved the replay to {}", path.display()),
+                Err(error) => log::error!("Couldn't save the replay: {}", error),
+            }
+        }
+    }
+
     // Handle keyboard and window events,
     // Exit the state if window close was requested,
     // or if user is holding the ESC key
@@ -90,10 +136,14 @@ impl<'a, 'b> SimpleState for GameplayState<'a, 'b> {
                 return Trans::Quit
             }
             if let Some(event) = get_key(&event) {
-                if event.0 == VirtualKeyCode::B && event.1 == ElementState::Released {
+                // Replays reset the level when it was reset in the recorded match
+                if event.0 == VirtualKeyCode::B && event.1 == ElementState::Released && !data.world.has_value::<ReplayPlayer>() {
                     // Reset the level
-                    data.world.write_resource::<MazeLevel>()
-                        .reset_timer.replace(0.1);
+                    data.world.write_resource::<Box<dyn Level>>()
+                        .reset_timer_mut().replace(0.1);
+                    if let Some(mut recorder) = data.world.try_fetch_mut::<ReplayRecorder>() {
+                        recorder.reset_level = true;
+                    }
                 }
                 if event.0 == VirtualKeyCode::H && event.1 == ElementState::Pressed {
                     use crate::utils::color::ColorschemeSet;
@@ -106,14 +156,60 @@ impl<'a, 'b> SimpleState for GameplayState<'a, 'b> {
 }
 
 
+/// Create the dispatcher running the game and set up its resources.
+/// A headless dispatcher leaves out systems that only change how the game looks
+pub fn build_dispatcher<'a, 'b>(world: &mut World, headless: bool) -> Dispatcher<'a, 'b> {
+    let mut builder = DispatcherBuilder::new()
+        // Step first, so that entities deleted in the last frame already lost their bodies
+        .with(physics::PhysicsEntitySystem::default(), "physics_entity_system", &[])
+        .with(physics::StepperSystem, "stepper_system", &["physics_entity_system"])
+        .with(physics::CollisionEventSystem, "collision_event_system", &["stepper_system"])
+
+        .with_barrier()
+        .with(systems::LevelSystem, "level_system", &[])
+        .with(systems::BotSystem, "bot_system", &["level_system"])
+        .with(systems::TankSystem, "tank_system", &[/*"input_system",*/ "level_system", "bot_system"])
+        .with(systems::SpawnSystem::default(), "spawn_system", &["level_system"])
+
+        // Weapons keep their state in their own components and only read tanks,
+        // so they only have to wait for the tanks' controls
+        .with(systems::BeamerSystem, "beamer_system", &["tank_system"])
+        .with(systems::CannonSystem, "cannon_system", &["tank_system"])
+        .with(systems::RocketSystem::default(), "rocket_system", &["tank_system"])
+        .with(systems::RailgunSystem, "railgun_system", &["tank_system"])
+        .with(systems::ShotgunSystem, "shotgun_system", &["tank_system"])
+        .with(systems::MineSystem::default(), "mine_system", &["tank_system"])
+        .with(systems::FragSystem, "frag_system", &["tank_system"])
+
+        .with(systems::DestroySystem::default(), "destroy_system", &["beamer_system", "cannon_system", "rocket_system", "railgun_system", "shotgun_system", "mine_system", "frag_system", "spawn_system"])
+        .with(systems::ScoreSystem::default(), "score_system", &["destroy_system"]);
+    if !headless {
+        builder.add(systems::CameraShakeSystem, "shake_system", &["destroy_system"]);
+        builder.add(systems::AmmoSystem, "ammo_system", &["destroy_system"]);
+    }
+    builder = builder
+        .with_barrier()
+        .with(physics::PTTSystem, "physics_to_transform_system", &[]);
+    if !headless {
+        builder.add_barrier();
+        builder.add(systems::ColorSystem, "color_system", &[]);
+    }
+
+    let mut dispatcher = builder
+        .with_pool((*world.read_resource::<ArcThreadPool>()).clone())
+        .build();
+    dispatcher.setup(world);
+    dispatcher
+}
+
 /// Initialize the level in the middle of the game's screen
-fn init_level(world: &mut World,  dimensions: &ScreenDimensions) {
+pub fn init_level(world: &mut World) {
     // It's up to this function which type and what size of level we should create
-    let maze = MazeLevel::new(world, dimensions);
-    world.insert(maze);
+    let level: Box<dyn Level> = Box::new(MazeLevel::new(world));
+    world.insert(level);
 }
 
-/// Initialize the UI score counters and the Scoreboard Resource
+/// Initialize the UI score counters, ammo counters and the Scoreboard Resource
 fn init_scoreboard(world: &mut World){
     // TODO_M: Config for text, general design
     let margin = 50.0;
@@ -125,39 +221,7 @@ fn init_scoreboard(world: &mut World){
 
     let default_color = [1.0, 1.0, 1.0, 1.0];
 
-    // TODO_H: Move those to ui/ asset files and load with UiLoader
-    let p1_text_trans = UiTransform::new(
-        "p1_text".to_string(), Anchor::BottomLeft, Anchor::BottomLeft,
-        margin,
-        margin, 
-        1.2,
-        text_width,
-        text_height,
-    );
-    let p1_score_trans = UiTransform::new(
-        "p1_text".to_string(), Anchor::BottomLeft, Anchor::BottomLeft,
-        p1_text_trans.local_x + p1_text_trans.width + padding,
-        margin, 
-        1.2,
-        score_width,
-        text_height,
-    );
-    let p2_text_trans = UiTransform::new(
-        "p2_text".to_string(), Anchor::BottomLeft, Anchor::BottomLeft,
-        p1_score_trans.local_x + p1_score_trans.width + padding,
-        margin, 
-        1.2,
-        text_width,
-        text_height,
-    );
-    let p2_score_trans = UiTransform::new(
-        "p2_score".to_string(), Anchor::BottomLeft, Anchor::BottomLeft,
-        p2_text_trans.local_x + p2_text_trans.width + padding,
-        margin, 
-        1.2,
-        score_width,
-        text_height,
-    );
+    let player_count = world.read_resource::<PlayersConfig>().player_count;
 
     // Load the font for the numbers
     let font = world.read_resource::<Loader>().load(
@@ -167,73 +231,96 @@ fn init_scoreboard(world: &mut World){
         &world.read_resource(),
     ); 
 
-    // Red tank's score counter
-    let _p1_text = world
-        .create_entity()
-        .with(p1_text_trans)
-        .with(UiText::new(
-            font.clone(),
-            "P1: ".to_string(),
-            default_color,
-            50.
-        ))
-        .with(Tint(Default::default()))
-        .with(DynamicColorMarker(ColorKey::P1))
-        .build();
-    let p1_score = world
-        .create_entity()
-        .with(p1_score_trans)
-        .with(UiText::new(
-            font.clone(),
-            "0".to_string(),
-            default_color,
-            50.
-        ))
-        .with(Tint(Default::default()))
-        .with(DynamicColorMarker(ColorKey::Text))
-        .build();
+    let scoring = world.read_resource::<MatchConfig>().scoring;
+    let mut scoreboard = Scoreboard::new(player_count, scoring);
 
-    let _p2_text = world
-        .create_entity()
-        .with(p2_text_trans)
-        .with(UiText::new(
-            font.clone(),
-            "P2: ".to_string(),
-            default_color,
-            50.
-        ))
-        .with(Tint(Default::default()))
-        .with(DynamicColorMarker(ColorKey::P2))
-        .build();
-    let p2_score = world
-        .create_entity()
-        .with(p2_score_trans)
-        .with(UiText::new(
-            font.clone(),
-            "0".to_string(),
-            default_color,
-            50.
-        ))
-        .with(Tint(Default::default()))
-        .with(DynamicColorMarker(ColorKey::Text))
-        .build();
+    // Every player gets a label and a score counter, placed one after another
+    // TODO_H: Move those to ui/ asset files and load with UiLoader
+    let mut x = margin;
+    for team in Team::ALL[..player_count].iter() {
+        let text_trans = UiTransform::new(
+            format!("{}_text", team.name().to_lowercase()), Anchor::BottomLeft, Anchor::BottomLeft,
+            x,
+            margin, 
+            1.2,
+            text_width,
+            text_height,
+        );
+        let score_trans = UiTransform::new(
+            format!("{}_score", team.name().to_lowercase()), Anchor::BottomLeft, Anchor::BottomLeft,
+            text_trans.local_x + text_trans.width + padding,
+            margin, 
+            1.2,
+            score_width,
+            text_height,
+        );
+        x = score_trans.local_x + score_trans.width + padding;
+
+        // The player's label, in the player's color
+        world
+            .create_entity()
+            .with(text_trans)
+            .with(UiText::new(
+                font.clone(),
+                format!("{}: ", team.name()),
+                default_color,
+                50.
+            ))
+            .with(Tint(Default::default()))
+            .with(DynamicColorMarker(ColorKey::from(*team)))
+            .build();
+        let score = world
+            .create_entity()
+            .with(score_trans)
+            .with(UiText::new(
+                font.clone(),
+                "0".to_string(),
+                default_color,
+                50.
+            ))
+            .with(Tint(Default::default()))
+            .with(DynamicColorMarker(ColorKey::Text))
+            .build();
+        scoreboard.texts.push(score);
+
+        // The player's ammo, above the label and the score
+        let ammo_trans = UiTransform::new(
+            format!("{}_ammo", team.name().to_lowercase()), Anchor::BottomLeft, Anchor::BottomLeft,
+            text_trans.local_x,
+            margin + text_height + padding,
+            1.2,
+            score_trans.local_x + score_trans.width - text_trans.local_x,
+            text_height,
+        );
+        world
+            .create_entity()
+            .with(ammo_trans)
+            .with(UiText::new(
+                font.clone(),
+                String::new(),
+                default_color,
+                30.
+            ))
+            .with(Tint(Default::default()))
+            .with(DynamicColorMarker(ColorKey::from(*team)))
+            .with(systems::AmmoText(*team))
+            .build();
+    }
     
     // Scoreboard resource
-    let mut scoreboard = Scoreboard::new();
-    scoreboard.texts.push(p1_score);
-    scoreboard.texts.push(p2_score);
     world.insert(scoreboard);
 }
 
-/// Create entities for both player's tanks
-fn init_players(world: &mut World) {
+/// Create entities for every player's tank
+pub fn init_players(world: &mut World) {
 
     // Fetch the config for tank's entities, it should be loaded on game data creation
     let tank_config = (*world.read_resource::<TankConfig>()).clone();
+    let players_config = (*world.read_resource::<PlayersConfig>()).clone();
+    let bot_config = (*world.read_resource::<BotConfig>()).clone();
 
-    //TODO: Create a trait for levels and change to <Level> (or Box<Level>?)
     // Fetch the level's starting positions (the Level should be crated before initializing players)
-    let starting_positions = world.read_resource::<MazeLevel>().starting_positions;
+    let starting_positions = world.read_resource::<Box<dyn Level>>().starting_positions().to_vec();
 
     // Create the SpriteRenders
     let sprites: Vec<SpriteRender> = tank_config.sprite_nums.iter()
@@ -241,39 +328,6 @@ fn init_players(world: &mut World) {
             sprite_sheet: world.fetch::<TanksSpriteSheet>().handle.clone(),
             sprite_number: *i,
         }).collect();
-    
-    // Set tanks' transforms to level's starting positions
-    // Red tank's Transform
-    let mut p1_transform = Transform::default();
-    p1_transform.set_translation(
-        amethyst::core::math::Vector3::new(
-            starting_positions[0].x,
-            starting_positions[0].y,
-            0.0
-        )
-    );
-    // Amethyst's Transform is in 3D, to create a 2D RigidBody we have to determine it's 2D translation + rotation
-    let p1_position: na::Isometry2<f32> = 
-        na::Isometry2::new(
-            na::Vector2::new(p1_transform.translation().x, p1_transform.translation().y),
-            p1_transform.rotation().angle(),
-        );
-
-    // Blue tank's Transform
-    let mut p2_transform = Transform::default();
-    p2_transform.set_translation(
-        amethyst::core::math::Vector3::new(
-            starting_positions[1].x,
-            starting_positions[1].y,
-            0.0
-        )
-    );
-    // Amethyst's Transform is in 3D, to create a 2D RigidBody we have to determine it's 2D translation + rotation
-    let p2_position: na::Isometry2<f32> = 
-        na::Isometry2::new(
-            na::Vector2::new(p2_transform.translation().x, p2_transform.translation().y),
-            p2_transform.rotation().angle(),
-        );
 
     // Create the shape for tanks
     let tank_shape = nc::shape::ShapeHandle::new(nc::shape::Cuboid::new(na::Vector2::new(
@@ -284,7 +338,7 @@ fn init_players(world: &mut World) {
     let tank_col_desc = np::object::ColliderDesc::new(tank_shape)
         .density(tank_config.density);
 
-    // Create the RigidBody description to be cloned for both tanks
+    // Create the RigidBody description to be cloned for every tank
     let mut tank_rb_desc = np::object::RigidBodyDesc::new();
     tank_rb_desc
         .set_max_linear_velocity(tank_config.max_linear_vel)
@@ -292,31 +346,6 @@ fn init_players(world: &mut World) {
         .set_linear_damping(tank_config.linear_damping)
         .set_angular_damping(tank_config.angular_damping);
 
-    let p1_body = physics::Body {
-        handle: world.fetch_mut::<physics::Physics>().add_rigid_body(
-            tank_rb_desc.clone()
-                .position(p1_position)
-                .build()
-        )
-    };
-    let p1_collider = physics::Collider {
-        handle: world.fetch_mut::<physics::Physics>().add_collider(
-            tank_col_desc.build(np::object::BodyPartHandle(p1_body.handle, 0))
-        )
-    };
-    let p2_body = physics::Body {
-        handle: world.fetch_mut::<physics::Physics>().add_rigid_body(
-            tank_rb_desc
-                .position(p2_position)
-                .build()
-        )
-    };
-    let p2_collider = physics::Collider {
-        handle: world.fetch_mut::<physics::Physics>().add_collider(
-            tank_col_desc.build(np::object::BodyPartHandle(p2_body.handle, 0))
-        )
-    };
-
     use crate::graphics::map_range;
     // Create TintBoxes for the tanks, so that only their bodies are colored.
     // TintBoxes are in texture coordinates (for now?) so we have to translate coords
@@ -328,27 +357,52 @@ fn init_players(world: &mut World) {
     let width = map_range(16., 0., tank_config.size_x as f32, 0.0, 1.0);
     let height = map_range(tank_config.size_y as f32, 0., tank_config.size_y as f32, 0.0, 1.0);
 
-    // Create the p1 tank
-    world.create_entity()
-        .with(Tank::new(Team::P1, Weapon::default()))
-        .with(sprites[0].clone())
-        .with(Tint(Default::default()))
-        .with(DynamicColorMarker(ColorKey::P1))
-        .with(TintBox([x, y, width, height]))
-        .with(p1_body)
-        .with(p1_collider)
-        .with(p1_transform)
-        .build();
-
-    // Create the p2 tank
-    world.create_entity()
-        .with(Tank::new(Team::P2, Weapon::default()))
-        .with(sprites[1].clone())
-        .with(Tint(Default::default()))
-        .with(DynamicColorMarker(ColorKey::P2))
-        .with(TintBox([x, y, width, height]))
-        .with(p2_body)
-        .with(p2_collider)
-        .with(p2_transform)
-        .build();
-}
\ No newline at end of file
+    for (index, team) in Team::ALL[..players_config.player_count].iter().enumerate() {
+        // Set the tank's transform to the level's starting position
+        let mut transform = Transform::default();
+        transform.set_translation(
+            amethyst::core::math::Vector3::new(
+                starting_positions[index].x,
+                starting_positions[index].y,
+                0.0
+            )
+        );
+        // Amethyst's Transform is in 3D, to create a 2D RigidBody we have to determine it's 2D translation + rotation
+        let position: na::Isometry2<f32> = 
+            na::Isometry2::new(
+                na::Vector2::new(transform.translation().x, transform.translation().y),
+                transform.rotation().angle(),
+            );
+
+        let body = physics::Body {
+            handle: world.fetch_mut::<physics::Physics>().add_rigid_body(
+                tank_rb_desc.clone()
+                    .position(position)
+                    .build()
+            )
+        };
+        let collider = physics::Collider {
+            handle: world.fetch_mut::<physics::Physics>().add_collider(
+                tank_col_desc.build(np::object::BodyPartHandle(body.handle, 0))
+            )
+        };
+
+        // Create the tank
+        let mut builder = world.create_entity();
+        // Let the computer drive the tank
+        if let Some(difficulty) = players_config.players[index].bot {
+            builder = builder.with(Bot::new(bot_config.skill(difficulty).clone()));
+        }
+        builder
+            .with(Tank::new(*team))
+            .with(CannonState::default())
+            .with(sprites[index % sprites.len()].clone())
+            .with(Tint(Default::default()))
+            .with(DynamicColorMarker(ColorKey::from(*team)))
+            .with(TintBox([x, y, width, height]))
+            .with(body)
+            .with(collider)
+            .with(transform)
+            .build();
+    }
+}
@@ -0,0 +1,226 @@
+use amethyst::{
+    prelude::*,
+    assets::{AssetStorage, Loader, ProgressCounter, Completion},
+    core::timing::Time,
+    ecs::Dispatcher,
+    input::{InputHandler, StringBindings},
+    renderer::{
+        types::{Mesh, TextureData},
+        rendy::mesh::MeshBuilder,
+        rendy::texture::TextureBuilder,
+        SpriteSheet, Texture,
+    },
+    window::{DisplayConfig, ScreenDimensions},
+    utils::application_dir,
+};
+use crate::graphics::{TintBox, ShapeRender, CircleMesh, QuadMesh};
+use crate::utils::{TanksSpriteSheet, SpawnsSpriteSheet};
+use crate::systems::camshake::CameraShake;
+use crate::config::{PlayersConfig, MatchConfig, MazeConfig};
+use crate::scoreboard::Scoreboard;
+use crate::physics;
+use crate::replay::GameRng;
+use crate::utils::mazegen::Maze;
+use super::loading::{load_resources, load_level_maps, check_level_maps};
+use super::gameplay::{build_dispatcher, init_level, init_players};
+
+/// Settings for a match played without a window
+#[derive(Debug, PartialEq)]
+pub struct HeadlessOptions {
+    /// The match ends after this many rounds
+    pub rounds: u32,
+    /// The match ends after this much game time (seconds) even if not all rounds were played,
+    /// so that bots that can't get to each other don't play forever
+    pub max_time: f32,
+}
+
+impl Default for HeadlessOptions {
+    fn default() -> Self {
+        HeadlessOptions {
+            rounds: 10,
+            max_time: 600.0,
+        }
+    }
+}
+
+impl HeadlessOptions {
+    /// Read the options from command line arguments: `--rounds <number>` and `--max-time <seconds>`
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut options = HeadlessOptions::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => (),
+                "--rounds" => options.rounds = parse_value(arg, args.next())?,
+                "--max-time" => options.max_time = parse_value(arg, args.next())?,
+                other => return Err(format!("Unknown argument {}", other)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(arg: &str, value: Option<&String>) -> Result<T, String> {
+    value
+        .ok_or_else(|| format!("{} needs a value", arg))?
+        .parse()
+        .map_err(|_| format!("Invalid value for {}", arg))
+}
+
+/// Plays a whole match without rendering anything, with bots controlling every tank.
+/// The world is stepped at the physics engine's fixed rate as fast as possible,
+/// and the final score gets reported once the match is over
+pub struct HeadlessState<'a, 'b> {
+    options: HeadlessOptions,
+    progress: ProgressCounter,
+    dispatcher: Option<Dispatcher<'a, 'b>>,
+    config_error: Option<String>,
+    /// Game time since the match started
+    elapsed: f32,
+}
+
+impl<'a, 'b> HeadlessState<'a, 'b> {
+    pub fn new(options: HeadlessOptions) -> Self {
+        HeadlessState {
+            options,
+            progress: ProgressCounter::new(),
+            dispatcher: None,
+            config_error: None,
+            elapsed: 0.0,
+        }
+    }
+}
+
+impl<'a, 'b> SimpleState for HeadlessState<'a, 'b> {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let world = data.world;
+
+        world.register::<TintBox>();
+        world.register::<ShapeRender>();
+        world.register::<physics::Body>();
+        world.register::<physics::Collider>();
+
+        world.insert(CameraShake::default());
+        world.insert(physics::Physics::new());
+        // There is no input, but the configs get validated against the bindings
+        world.insert(InputHandler::<StringBindings>::new());
+
+        if let Err(error) = load_resources(world, true) {
+            self.config_error = Some(error);
+            return;
+        }
+
+        // The level is placed in the middle of the screen it would normally be shown on
+        let display_config = match application_dir("res/config/display.ron")
+            .map_err(|error| error.to_string())
+            .and_then(|path| DisplayConfig::load(path).map_err(|error| error.to_string()))
+        {
+            Ok(display_config) => display_config,
+            Err(error) => {
+                self.config_error = Some(format!("display.ron: {}", error));
+                return;
+            },
+        };
+        let (width, height) = display_config.dimensions.unwrap_or((800, 600));
+        world.insert(ScreenDimensions::new(width, height, 1.0));
+
+        insert_placeholder_assets(world);
+        if let Err(error) = load_level_maps(world, &mut self.progress) {
+            self.config_error = Some(error);
+        }
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        if let Some(ref error) = self.config_error {
+            println!("Failed loading config: {}", error);
+            return Trans::Quit;
+        }
+        match self.progress.complete() {
+            Completion::Failed => {
+                println!("Failed loading assets: {:?}", self.progress.errors());
+                return Trans::Quit;
+            },
+            Completion::Loading => return Trans::None,
+            Completion::Complete => (),
+        }
+
+        let world = &mut *data.world;
+        if self.dispatcher.is_none() {
+            // Everything is loaded, start the match
+            let player_count = world.read_resource::<PlayersConfig>().player_count;
+            if let Err(error) = check_level_maps(world, &world.read_resource::<MazeConfig>(), player_count) {
+                self.config_error = Some(error);
+                return Trans::None;
+            }
+            world.insert(GameRng::new(Maze::random_seed()));
+            self.dispatcher = Some(build_dispatcher(world, true));
+            init_level(world);
+            init_players(world);
+            let scoring = world.read_resource::<MatchConfig>().scoring;
+            world.insert(Scoreboard::new(player_count, scoring));
+            return Trans::None;
+        }
+        let dispatcher = self.dispatcher.as_mut().unwrap();
+
+        // Every frame simulates exactly one physics step, no matter how long it really took
+        let timestep = world.read_resource::<physics::Physics>().timestep();
+        world.write_resource::<Time>().set_delta_seconds(timestep);
+        dispatcher.dispatch(world);
+        self.elapsed += timestep;
+
+        let scoreboard = world.read_resource::<Scoreboard>();
+        if scoreboard.rounds() >= self.options.rounds || self.elapsed >= self.options.max_time {
+            println!("Played {} rounds in {:.1} seconds of game time", scoreboard.rounds(), self.elapsed);
+            for team in scoreboard.teams() {
+                let stats = scoreboard.get_stats(*team);
+                println!(
+                    "{}: {} (kills: {}, deaths: {}, suicides: {}, rounds won: {})",
+                    team.name(), scoreboard.get_score(*team), stats.kills, stats.deaths, stats.suicides, stats.wins
+                );
+            }
+            return Trans::Quit;
+        }
+        Trans::None
+    }
+}
+
+/// Nothing gets drawn when running headless, but systems still give entities meshes and sprites.
+/// They get handles to empty assets, which never get loaded because there is no renderer to process them
+fn insert_placeholder_assets(world: &mut World) {
+    world.insert(AssetStorage::<Mesh>::default());
+    world.insert(AssetStorage::<Texture>::default());
+    world.insert(AssetStorage::<SpriteSheet>::default());
+
+    let (quad, circle, sprite_sheet) = {
+        let loader = world.read_resource::<Loader>();
+        let quad = loader.load_from_data::<Mesh, _>(MeshBuilder::new().into(), (), &world.read_resource());
+        let circle = loader.load_from_data::<Mesh, _>(MeshBuilder::new().into(), (), &world.read_resource());
+        let texture = loader.load_from_data::<Texture, _>(TextureData::from(TextureBuilder::new()), (), &world.read_resource());
+        let sprite_sheet = loader.load_from_data::<SpriteSheet, _>(
+            SpriteSheet { texture, sprites: Vec::new() },
+            (),
+            &world.read_resource()
+        );
+        (quad, circle, sprite_sheet)
+    };
+
+    world.insert(QuadMesh { handle: quad });
+    world.insert(CircleMesh { handle: circle });
+    world.insert(TanksSpriteSheet::new(sprite_sheet.clone()));
+    world.insert(SpawnsSpriteSheet::new(sprite_sheet));
+}
+
+#[test]
+/// Headless options have defaults and complain about arguments they don't know
+fn test_headless_options() {
+    let args = |line: &str| line.split_whitespace().map(String::from).collect::<Vec<_>>();
+
+    assert_eq!(HeadlessOptions::from_args(&args("--headless")), Ok(HeadlessOptions::default()));
+    assert_eq!(
+        HeadlessOptions::from_args(&args("--headless --rounds 3 --max-time 90")),
+        Ok(HeadlessOptions { rounds: 3, max_time: 90.0 })
+    );
+    assert!(HeadlessOptions::from_args(&args("--headless --rounds")).is_err());
+    assert!(HeadlessOptions::from_args(&args("--headless --rounds many")).is_err());
+    assert!(HeadlessOptions::from_args(&args("--headless --fast")).is_err());
+}
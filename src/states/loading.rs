@@ -17,20 +17,35 @@ use amethyst::{
         Camera, ImageFormat, SpriteSheet, SpriteSheetFormat, Texture
     },
     window::ScreenDimensions,
+    input::{InputHandler, StringBindings},
     utils::application_dir
 };
 use crate::graphics::{TintBox, ShapeRender, CircleMesh, QuadMesh};
 use crate::utils::{TanksSpriteSheet, SpawnsSpriteSheet, color::Colorscheme, color::ColorschemeSet};
+use crate::utils::levelmap::{LevelMap, LevelMapSet};
 use crate::markers::{DynamicColorMarker, ColorKey};
 use crate::systems::camshake::CameraShake;
 
 use crate::config;
+use crate::replay::Replay;
 
 use crate::physics;
 use super::GameplayState;
 
 pub struct LoadingState {
     progress: ProgressCounter,
+    config_error: Option<String>,
+    /// A replay to play once everything is loaded, instead of starting a new match
+    replay: Option<Replay>,
+}
+
+impl LoadingState {
+    pub fn with_replay(replay: Replay) -> Self {
+        LoadingState {
+            replay: Some(replay),
+            ..Default::default()
+        }
+    }
 }
 
 impl SimpleState for LoadingState {
@@ -75,7 +90,9 @@ impl SimpleState for LoadingState {
             (quad, circle)
         });
 
-        load_resources(world);
+        if let Err(error) = load_resources(world, false) {
+            self.config_error = Some(error);
+        }
 
         world.insert(QuadMesh { handle: quad });
         world.insert(CircleMesh { handle: circle });
@@ -92,6 +109,9 @@ impl SimpleState for LoadingState {
         init_background(world, &dimensions);
 
         load_colorschemes(world, &mut self.progress);
+        if let Err(error) = load_level_maps(world, &mut self.progress) {
+            self.config_error = Some(error);
+        }
 
         // Load our sprite sheets
         let tanks_sprite_sheet = TanksSpriteSheet::new(load_sprite_sheet(world, "tanks", &mut self.progress));
@@ -104,19 +124,39 @@ impl SimpleState for LoadingState {
     }
 
     fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        if let Some(ref error) = self.config_error {
+            println!("Failed loading config: {}", error);
+            return Trans::Quit;
+        }
         match self.progress.complete() {
             Completion::Failed => {
                 println!("Failed loading assets: {:?}", self.progress.errors());
                 Trans::Quit
             },
             Completion::Complete => {
+                // A replay plays with the maze config it was recorded with
+                let checked = match &self.replay {
+                    Some(replay) => check_level_maps(data.world, &replay.configs.maze, replay.configs.players.player_count),
+                    None => check_level_maps(
+                        data.world,
+                        &data.world.read_resource::<config::MazeConfig>(),
+                        data.world.read_resource::<config::PlayersConfig>().player_count,
+                    ),
+                };
+                if let Err(error) = checked {
+                    self.config_error = Some(error);
+                    return Trans::None;
+                }
                 println!("Assets loaded, transitioning to Gameplay");
 
                 // Delete loading text
                 let loading_text_entity = data.world.exec(|finder: UiFinder<'_>| finder.find("loading_text").unwrap());
                 data.world.delete_entity(loading_text_entity).unwrap();
 
-                Trans::Switch(Box::new(GameplayState::default()))
+                match self.replay.take() {
+                    Some(replay) => Trans::Switch(Box::new(GameplayState::with_replay(replay))),
+                    None => Trans::Switch(Box::new(GameplayState::default())),
+                }
             }
             Completion::Loading => Trans::None
         }
@@ -204,24 +244,56 @@ fn init_camera(world: &mut World, dimensions: &ScreenDimensions) {
         .build();
 }
 
-fn load_resources(world: &mut World) {
+/// Load config files and insert them as resources, fails if a config is inconsistent.
+/// Nobody can play when running headless, so then every player is replaced by a bot
+pub(super) fn load_resources(world: &mut World, headless: bool) -> Result<(), String> {
     let config = application_dir("res/config").unwrap();
     let tank_config         = config::TankConfig    ::load(&config.join("tank.ron"      )).unwrap();
     let maze_config         = config::MazeConfig    ::load(&config.join("maze.ron"      )).unwrap();
     let beamer_config       = config::BeamerConfig  ::load(&config.join("beamer.ron"    )).unwrap();
     let cannon_config       = config::CannonConfig  ::load(&config.join("cannon.ron"    )).unwrap();
+    let rocket_config       = config::RocketConfig  ::load(&config.join("rocket.ron"    )).unwrap();
+    let railgun_config      = config::RailgunConfig ::load(&config.join("railgun.ron"   )).unwrap();
+    let shotgun_config      = config::ShotgunConfig ::load(&config.join("shotgun.ron"   )).unwrap();
+    let mine_config         = config::MineConfig    ::load(&config.join("mine.ron"      )).unwrap();
+    let frag_config         = config::FragConfig    ::load(&config.join("frag.ron"      )).unwrap();
     let spawn_config        = config::SpawnConfig   ::load(&config.join("spawn.ron"     )).unwrap();
     let destroy_config      = config::DestroyConfig ::load(&config.join("destroy.ron"   )).unwrap();
+    let match_config        = config::MatchConfig   ::load(&config.join("match.ron"     )).unwrap();
+    let mut players_config  = config::PlayersConfig ::load(&config.join("players.ron"   )).unwrap();
+    let bot_config          = config::BotConfig     ::load(&config.join("bot.ron"       )).unwrap();
+    let replay_config       = config::ReplayConfig  ::load(&config.join("replay.ron"    )).unwrap();
 
     let performance_config  = config::PerformanceConfig::load(&config.join( "performance.ron")).unwrap();
 
+    if headless {
+        for player in players_config.players.iter_mut().filter(|player| player.bot.is_none()) {
+            player.bot = Some(config::BotDifficulty::Normal);
+        }
+    }
+    // Report missing controls now rather than in the middle of a game
+    players_config.validate(&world.read_resource::<InputHandler<StringBindings>>().bindings)?;
+    maze_config.validate(players_config.player_count)?;
+    shotgun_config.validate()?;
+
     world.insert(tank_config);
     world.insert(maze_config);
     world.insert(beamer_config);
     world.insert(cannon_config);
+    world.insert(rocket_config);
+    world.insert(railgun_config);
+    world.insert(shotgun_config);
+    world.insert(mine_config);
+    world.insert(frag_config);
     world.insert(spawn_config);
     world.insert(destroy_config);
+    world.insert(match_config);
+    world.insert(players_config);
+    world.insert(bot_config);
+    world.insert(replay_config);
+    world.write_resource::<physics::Physics>().set_timestep(performance_config.physics_timestep);
     world.insert(performance_config);
+    Ok(())
 }
 
 use std::fs;
@@ -255,10 +327,62 @@ fn load_colorschemes(world: &mut World, progress: &mut ProgressCounter) {
     world.insert(colorscheme_set);
 }
 
+/// Check that every map the maze config plays was found in `res/levels/`, loaded,
+/// and has a cell for every playing tank to start in
+pub(super) fn check_level_maps(world: &World, maze_config: &config::MazeConfig, player_count: usize) -> Result<(), String> {
+    let map_storage = world.read_resource::<AssetStorage<LevelMap>>();
+    let level_maps = world.read_resource::<LevelMapSet>();
+    for name in maze_config.source.map_names() {
+        match level_maps.get(name, &map_storage) {
+            None => return Err(format!("Level map \"{}\" not found in res/levels", name)),
+            Some(map) if map.cell_count() < player_count => return Err(format!(
+                "Level map \"{}\" doesn't have room for {} players", name, player_count
+            )),
+            Some(_) => (),
+        }
+    }
+    Ok(())
+}
+
+/// Load every hand-authored level from `res/levels/`,
+/// so that they can be picked by their file names. Without the directory there are no levels
+pub(super) fn load_level_maps(world: &mut World, progress: &mut ProgressCounter) -> Result<(), String> {
+    let mut level_map_set = LevelMapSet::new();
+
+    let dir = application_dir("res/levels").map_err(|error| format!("res/levels: {}", error))?;
+    let paths = match fs::read_dir(dir) {
+        Ok(paths) => paths.collect::<Result<Vec<_>, _>>().map_err(|error| format!("res/levels: {}", error))?,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => vec![],
+        Err(error) => return Err(format!("res/levels: {}", error)),
+    };
+
+    for file in paths {
+        let is_file = file.file_type().map_err(|error| format!("res/levels: {}", error))?.is_file();
+        if is_file {
+            let name = file.file_name().into_string()
+                .map_err(|name| format!("res/levels: invalid file name {:?}", name))?;
+            let actual_name = name.split('.').next().unwrap();
+
+            let map = world.read_resource::<Loader>().load(
+                format!("levels/{}.ron", actual_name),
+                amethyst::assets::RonFormat,
+                &mut (*progress),
+                &world.read_resource::<AssetStorage<LevelMap>>(),
+            );
+            level_map_set.add_map(actual_name.to_string(), map);
+        }
+    }
+
+    world.insert(level_map_set);
+    Ok(())
+}
+
 impl Default for LoadingState {
     fn default() -> Self {
         LoadingState {
-            progress: ProgressCounter::new()
+            progress: ProgressCounter::new(),
+            config_error: None,
+            replay: None,
         }
     }
 }
\ No newline at end of file
@@ -1,5 +1,7 @@
 mod gameplay;
 mod loading;
+mod headless;
 
 pub use gameplay::GameplayState;
-pub use loading::LoadingState;
\ No newline at end of file
+pub use loading::LoadingState;
+pub use headless::{HeadlessState, HeadlessOptions};
@@ -0,0 +1,72 @@
+use amethyst::{
+    ecs::{
+        Component, DenseVecStorage,
+        Entities, Join, System,
+        ReadStorage, WriteStorage, ReadExpect,
+    },
+    ui::UiText,
+};
+use crate::tank::{Tank, Team};
+use crate::weapons::{self, Projectile, Weapon};
+use crate::config::{CannonConfig, ShotgunConfig, RocketConfig, MineConfig};
+
+/// A Component for texts showing how much ammo a team's tank has left
+pub struct AmmoText(pub Team);
+
+impl Component for AmmoText {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Shows how many more shots every tank has, one bar for every shot.
+/// Weapons that don't run out of ammo show nothing
+pub struct AmmoSystem;
+
+impl<'s> System<'s> for AmmoSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'s>,
+        ReadStorage<'s, Tank>,
+        ReadStorage<'s, Projectile>,
+        ReadStorage<'s, AmmoText>,
+        WriteStorage<'s, UiText>,
+
+        ReadExpect<'s, CannonConfig>,
+        ReadExpect<'s, ShotgunConfig>,
+        ReadExpect<'s, RocketConfig>,
+        ReadExpect<'s, MineConfig>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            tanks,
+            projectiles,
+            ammo_texts,
+            mut ui_texts,
+            cannon_config,
+            shotgun_config,
+            rocket_config,
+            mine_config,
+        ): Self::SystemData,
+    ) {
+        for (ammo_text, ui_text) in (&ammo_texts, &mut ui_texts).join() {
+            let tank = (&entities, &tanks).join().find(|(_, tank)| tank.team == ammo_text.0);
+            let shots = tank.and_then(|(entity, tank)| {
+                let live = weapons::live_projectiles(entity, tank.weapon, (&projectiles).join());
+                match tank.weapon {
+                    Weapon::Cannon => Some(cannon_config.max_bullets.saturating_sub(live)),
+                    // Every shot fires all of the pellets
+                    Weapon::Shotgun => Some(shotgun_config.max_pellets.saturating_sub(live) / shotgun_config.pellet_count as usize),
+                    Weapon::Rocket => Some(rocket_config.max_rockets.saturating_sub(live)),
+                    Weapon::Mine => Some(mine_config.max_mines.saturating_sub(live)),
+                    Weapon::Beamer | Weapon::Railgun | Weapon::Frag => None,
+                }
+            });
+            let text = shots.map_or_else(String::new, |shots| "|".repeat(shots));
+            if ui_text.text != text {
+                ui_text.text = text;
+            }
+        }
+    }
+}
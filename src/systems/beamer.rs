@@ -8,14 +8,14 @@ use amethyst::{
     window::ScreenDimensions,
     ecs::{
         Join, System,
-        Read, WriteStorage, ReadExpect, WriteExpect,
+        Read, ReadStorage, WriteStorage, ReadExpect, WriteExpect,
         Entities, Entity
     }
 };
 use crate::graphics::{ShapeRender, QuadMesh};
 use crate::tank::{Tank, TankState};
 use crate::physics;
-use crate::weapons::Weapon;
+use crate::weapons::{BeamerState, Projectile, Weapon, ricochet};
 use crate::config::TankConfig;
 use crate::config::BeamerConfig;
 use crate::markers::*;
@@ -26,7 +26,9 @@ pub struct BeamerSystem;
 impl<'s> System<'s> for BeamerSystem {
     #[allow(clippy::type_complexity)]
     type SystemData = (
-        WriteStorage<'s, Tank>,
+        ReadStorage<'s, Tank>,
+        WriteStorage<'s, BeamerState>,
+        WriteStorage<'s, Projectile>,
         WriteExpect<'s, physics::Physics>,
         WriteStorage<'s, physics::Body>,
         WriteStorage<'s, physics::Collider>,
@@ -52,7 +54,9 @@ impl<'s> System<'s> for BeamerSystem {
     fn run(
         &mut self,
         (
-            mut tanks,
+            tanks,
+            mut beamers,
+            mut projectiles,
             mut physics,
             mut bodies,
             mut colliders,
@@ -73,77 +77,72 @@ impl<'s> System<'s> for BeamerSystem {
         ): Self::SystemData,
     ) {
 
+        // We don't want to see unused beam segments, so we keep them safely off-screen
+        let hidden_pos = na::Isometry2::translation(-1000.0, 0.0);
+        // The beam can't be longer than the diagonal of our screen;
+        // we want the players to think the beam is infinite, so the beam's end can be just off-screen
+        let beam_range = screen_dimensions.diagonal().norm();
+
         // Entities and Bodies to be added to them because we can't borrow bodies twice in the same scope
         let mut bodies_to_add: Vec<(Entity, physics::Body)> = Vec::new();
-        for (tank, body) in (&mut tanks, &bodies).join() {
-            if let Weapon::Beamer {
-                    ref mut heating_progress,
-                    ref mut overheat_timer,
-                    ref mut shooting_timer,
-                    ref mut heating_square,
-                    ref mut beam, 
-            } = tank.weapon {
-                // The player is holding the shoot button and isn't destroyed 
-                if tank.is_shooting && tank.state == TankState::Alive {
-                    // If the weapon can shoot and the weapon is not ready to shoot
-                    if *heating_progress < 1.0 && overheat_timer.is_none() && shooting_timer.is_none() {
-
-                        *heating_progress += time.delta_seconds() / beamer_config.heat_time;
-
-                        if heating_square.is_none() {
-                            // Initialize the heating square
-                            let shape_render = ShapeRender { mesh: quad_mesh.handle.clone() };
-                            // The transform will be set and updated later so it moves with the player
-                            let mut square_transform = Transform::default();
-                            // Make the square appear over the tank sprite and over wall sprites
-                            square_transform.set_translation_z(0.10);
-                            // The heating square is a purely cosmetic entity
-                            // IDEA: Make the heating square also a sensor so the tanks can run into each other
-                            //       while heating their beamers without actually shooting them to kill the other player
-                            // REVIEW: We don't really want to do this. It introduces another collider that slows down the simulation,
-                            //         AND we would have to introduce collision groups because it would constantly collide with
-                            //         it's own tank and walls. It would be a neat detail, but makes a lot of things more wonky and weird.
-                            let square_entity = entities
-                                .build_entity()
-                                .with(square_transform, &mut transforms)
-                                .with(shape_render, &mut shape_renders)
-                                .with(Tint(Default::default()), &mut tints)
-                                .with(DynamicColorMarker(ColorKey::from(tank.team)), &mut dyn_color_markers)
-                                .with(TempMarker(None), &mut temp_markers)
-                                .build();
-                            
-                            heating_square.replace(square_entity);
-                        }
+        for (entity, tank, beamer, body) in (&entities, &tanks, &mut beamers, &bodies).join() {
+            let BeamerState {
+                ref mut heating_progress,
+                ref mut overheat_timer,
+                ref mut shooting_timer,
+                ref mut heating_square,
+                ref mut beam,
+            } = *beamer;
+            // The player is holding the shoot button and isn't destroyed 
+            if tank.is_shooting && tank.state == TankState::Alive {
+                // If the weapon can shoot and the weapon is not ready to shoot
+                if *heating_progress < 1.0 && overheat_timer.is_none() && shooting_timer.is_none() {
+
+                    *heating_progress += time.delta_seconds() / beamer_config.heat_time;
+
+                    if heating_square.is_none() {
+                        // Initialize the heating square
+                        let shape_render = ShapeRender { mesh: quad_mesh.handle.clone() };
+                        // The transform will be set and updated later so it moves with the player
+                        let mut square_transform = Transform::default();
+                        // Make the square appear over the tank sprite and over wall sprites
+                        square_transform.set_translation_z(0.10);
+                        // The heating square is a purely cosmetic entity
+                        // IDEA: Make the heating square also a sensor so the tanks can run into each other
+                        //       while heating their beamers without actually shooting them to kill the other player
+                        // REVIEW: We don't really want to do this. It introduces another collider that slows down the simulation,
+                        //         AND we would have to introduce collision groups because it would constantly collide with
+                        //         it's own tank and walls. It would be a neat detail, but makes a lot of things more wonky and weird.
+                        let square_entity = entities
+                            .build_entity()
+                            .with(square_transform, &mut transforms)
+                            .with(shape_render, &mut shape_renders)
+                            .with(Tint(Default::default()), &mut tints)
+                            .with(DynamicColorMarker(ColorKey::from(tank.team)), &mut dyn_color_markers)
+                            .with(TempMarker(None), &mut temp_markers)
+                            .build();
+                        
+                        heating_square.replace(square_entity);
+                    }
 
-                        // If the weapon is done heating up
-                        if *heating_progress >= 1.0 {
-                            // Shoot
+                    // If the weapon is done heating up
+                    if *heating_progress >= 1.0 {
+                        // Shoot
 
-                            // Create the beam entity
+                        // Create an entity for every segment of the beam, the first one starts at the tank
+                        // and every other one after a reflection. Their lengths get set and updated later
+                        for _ in 0..=beamer_config.reflections {
                             let shape_render = ShapeRender { mesh: quad_mesh.handle.clone() };
 
-                            // Calculate the beam length so that it's equal or more than the diagonal of our screen;
-                            // we want the players to think the beam is infinite, so the beam's end can be just off-screen
-                            let beam_length = screen_dimensions.diagonal().norm();
-                            // Because the sprite is just one pixel, calculate the scale needed to make it the correct size
-                            let scale = amethyst::core::math::Vector3::new(
-                                beamer_config.beam_width,
-                                beam_length,
-                                1.0
-                            );
-
                             // The position will be set and updated later
                             let mut beam_transform = Transform::default();
-                            beam_transform.set_scale(scale);
+                            beam_transform.set_scale(amethyst::core::math::Vector3::new(beamer_config.beam_width, 1.0, 1.0));
                             // Make the beam appear over the wall sprites
                             beam_transform.set_translation_z(0.2);
-                            // We don't want to see the beam until it's body position gets updated, so we set the
-                            // initial position to something safely off-screen
-                            let tmp_pos = na::Isometry2::translation(-1000.0, 0.0);
 
                             // Create a sensor for the beam for detecting physics bodies in the beam
-                            let shape = nc::shape::ShapeHandle::new(nc::shape::Cuboid::new(na::Vector2::new(scale.x/2.0, scale.y/2.0)));
-                            let body = np::object::RigidBodyDesc::new().status(np::object::BodyStatus::Kinematic).position(tmp_pos).build();
+                            let shape = nc::shape::ShapeHandle::new(nc::shape::Cuboid::new(na::Vector2::new(beamer_config.beam_width / 2.0, 0.5)));
+                            let body = np::object::RigidBodyDesc::new().status(np::object::BodyStatus::Kinematic).position(hidden_pos).build();
                             let body_handle = physics.add_rigid_body(body);
                             let sensor = np::object::ColliderDesc::new(shape).sensor(true).build(np::object::BodyPartHandle(body_handle, 0));
                             let sensor_handle = physics.add_collider(sensor);
@@ -156,6 +155,7 @@ impl<'s> System<'s> for BeamerSystem {
                                 .with(DynamicColorMarker(ColorKey::from(tank.team)), &mut dyn_color_markers)
                                 .with(TempMarker(None), &mut temp_markers)
                                 .with(DeadlyMarker, &mut deadly_markers)
+                                .with(Projectile { owner: entity, weapon: Weapon::Beamer }, &mut projectiles)
                                 .with(physics::Collider::new(sensor_handle), &mut colliders)
                                 // We would do that but we already borrowed bodies, so we have to build the entity now and add the body later
                                 //.with(physics::Body{handle: body_handle}, &mut bodies)
@@ -163,94 +163,115 @@ impl<'s> System<'s> for BeamerSystem {
 
                             bodies_to_add.push((beam_entity, physics::Body{handle: body_handle}));
 
-                            beam.replace(beam_entity);
+                            beam.push(beam_entity);
+                        }
 
-                            // Recoil
-                            // TODO: Steady force pushing the tank opposite to the shooting direction would be fun
+                        // Recoil
+                        // TODO: Steady force pushing the tank opposite to the shooting direction would be fun
 
-                            // Start shooting timer
-                            shooting_timer.replace(beamer_config.shoot_time);
+                        // Start shooting timer
+                        shooting_timer.replace(beamer_config.shoot_time);
 
-                            // Shake the camera because why not
-                            cam_shake.dms.push((beamer_config.shoot_time, beamer_config.shake_magnitude))
-                        }
-                    } 
-                }
+                        // Shake the camera because why not
+                        cam_shake.dms.push((beamer_config.shoot_time, beamer_config.shake_magnitude))
+                    }
+                } 
+            }
 
-                // Update things related to the weapon
-
-                let body = physics.get_rigid_body_mut(body.handle).unwrap();
-                if let Some(square) = heating_square {
-                    // Update the heating square's transform
-                    // TODO_VL: Clean up
-                    let rotation = na::UnitQuaternion::from_axis_angle(&na::Vector::z_axis(), body.position().rotation.angle());
-                    // TODO: Removing this is impossible until nalgebra versions from Amethyst and NPhysics match
-                    let amethyst_rotation = amethyst::core::math::UnitQuaternion::from_axis_angle(&amethyst::core::math::Vector::z_axis(), body.position().rotation.angle());
-                    let trans = body.position().translation.vector.push(0.1)
-                        + rotation * na::Vector3::<f32>::new(0.0, tank_config.size_y as f32 / 2.0, 0.1);
-
-                    let scale = *heating_progress * beamer_config.heating_max_scale;
-
-                    transforms.get_mut(*square).unwrap()
-                        .set_translation_xyz(trans.x, trans.y, trans.z)
-                        .set_rotation(amethyst_rotation)
-                        .prepend_rotation_z_axis(45.0_f32.to_radians())
-                        .set_scale(amethyst::core::math::Vector3::new(scale, scale, 1.0));
-
-                    // The beam only exists if the heating square exists
-                    // and we can put the check here so we can use the rotations
-                    // we calculated earlier
-                    if let Some(beam) = beam {
-                        // Update the beam's position
-                        // The beam is bound to a physics body (beacause it has a sensor collider)
-                        // so we have to update its position by the body
+            // Update things related to the weapon
+
+            let tank_pose = *physics.get_rigid_body(body.handle).unwrap().position();
+            if let Some(square) = heating_square {
+                // Update the heating square's transform
+                // TODO_VL: Clean up
+                let rotation = na::UnitQuaternion::from_axis_angle(&na::Vector::z_axis(), tank_pose.rotation.angle());
+                // TODO: Removing this is impossible until nalgebra versions from Amethyst and NPhysics match
+                let amethyst_rotation = amethyst::core::math::UnitQuaternion::from_axis_angle(&amethyst::core::math::Vector::z_axis(), tank_pose.rotation.angle());
+                let trans = tank_pose.translation.vector.push(0.1)
+                    + rotation * na::Vector3::<f32>::new(0.0, tank_config.size_y as f32 / 2.0, 0.1);
+
+                let scale = *heating_progress * beamer_config.heating_max_scale;
+
+                transforms.get_mut(*square).unwrap()
+                    .set_translation_xyz(trans.x, trans.y, trans.z)
+                    .set_rotation(amethyst_rotation)
+                    .prepend_rotation_z_axis(45.0_f32.to_radians())
+                    .set_scale(amethyst::core::math::Vector3::new(scale, scale, 1.0));
+
+                // The beam only exists if the heating square exists
+                if !beam.is_empty() {
+                    // The beam stops at the first wall, or reflects off of it
+                    let (path, _) = ricochet::ray_path(
+                        &physics,
+                        tank_pose * na::Point2::new(0.0, (tank_config.size_y as f32 / 2.0) + beamer_config.self_safety_margin),
+                        tank_pose.rotation * na::Vector2::new(0.0, 1.0),
+                        beamer_config.reflections,
+                        beam_range,
+                        None,
+                        // Tanks don't stop the beam, it goes through all of them
+                        |_| false,
+                    );
+
+                    // Update the segments' positions and lengths
+                    // The beam is bound to physics bodies (beacause it has sensor colliders)
+                    // so we have to update its position by the bodies
+                    for (i, segment) in beam.iter().enumerate() {
                         // There is a single frame where the player shot but we havent't added the
-                        // body handle for the beam yet
-                        if let Some(beam_pbody) = bodies.get(*beam) {
-                            let rotation = na::UnitComplex::from_angle(body.position().rotation.angle());
-                            let trans = body.position().translation.vector
-                                + rotation * na::Vector2::new(0.0, (tank_config.size_y as f32 / 2.0) + (transforms.get(*beam).unwrap().scale().y / 2.0) + beamer_config.self_safety_margin);
-                            let angle = body.position().rotation.angle();
-                            physics.get_rigid_body_mut(beam_pbody.handle).unwrap()
-                                .set_position(
-                                    na::Isometry2::new(
-                                        trans.xy(),
-                                        angle
-                                    )
-                                );
+                        // body handles for the beam yet
+                        let (body_handle, collider_handle) = match (bodies.get(*segment), colliders.get(*segment)) {
+                            (Some(body), Some(collider)) => (body.handle, collider.handle),
+                            _ => continue,
+                        };
+                        let offset = match path.get(i..i + 2) {
+                            Some(points) => points[1] - points[0],
+                            None => na::Vector2::zeros(),
+                        };
+                        let length = offset.norm();
+                        if length <= std::f32::EPSILON {
+                            // The beam stopped before reaching this segment
+                            physics.teleport_rigid_body(body_handle, hidden_pos);
+                            continue;
+                        }
+                        let middle = path[i] + offset / 2.0;
+                        // The segment's length is along its y axis
+                        physics.teleport_rigid_body(body_handle, na::Isometry2::new(middle.coords, (-offset.x).atan2(offset.y)));
+                        physics.get_collider_mut(collider_handle).unwrap()
+                            .set_shape(nc::shape::ShapeHandle::new(nc::shape::Cuboid::new(na::Vector2::new(beamer_config.beam_width / 2.0, length / 2.0))));
+                        if let Some(transform) = transforms.get_mut(*segment) {
+                            transform.set_scale(amethyst::core::math::Vector3::new(beamer_config.beam_width, length, 1.0));
                         }
                     }
                 }
-                
-                if let Some(timer) = shooting_timer {
-
-                    // Decrease the shooting timer
-                    *timer -= time.delta_seconds();
-
-                    // If the timer reached zero
-                    if *timer <= 0.0 {
-                        // Reset the heating progress
-                        *heating_progress = 0.0;
-                        // Remove the beam and the heating square
-                        // TODO: Do a vanishing animation
-                        physics.remove_collider(colliders.get(beam.unwrap()).unwrap().handle);
-                        entities.delete(heating_square.unwrap()).expect("Couldn't remove heating square entity");
-                        entities.delete(beam.unwrap()).expect("Couldn't remove beam entity");
-                        *heating_square = None;
-                        *beam = None;
-                        *shooting_timer = None;
-                        // Start overheat timer
-                        overheat_timer.replace(beamer_config.overheat_time);
+            }
+            
+            if let Some(timer) = shooting_timer {
+
+                // Decrease the shooting timer
+                *timer -= time.delta_seconds();
+
+                // If the timer reached zero
+                if *timer <= 0.0 {
+                    // Reset the heating progress
+                    *heating_progress = 0.0;
+                    // Remove the beam and the heating square
+                    // TODO: Do a vanishing animation
+                    entities.delete(heating_square.unwrap()).expect("Couldn't remove heating square entity");
+                    for segment in beam.drain(..) {
+                        entities.delete(segment).expect("Couldn't remove beam entity");
                     }
+                    *heating_square = None;
+                    *shooting_timer = None;
+                    // Start overheat timer
+                    overheat_timer.replace(beamer_config.overheat_time);
                 }
-                if let Some(timer) = overheat_timer {
-                    // Decrease the overheat timer
-                    *timer -= time.delta_seconds();
-                    if *timer <= 0.0 { *overheat_timer = None; }
-                }                
-                if *heating_progress > 0.0 && !tank.is_shooting && shooting_timer.is_none() {
-                    *heating_progress -= time.delta_seconds() / beamer_config.heat_time;
-                }
+            }
+            if let Some(timer) = overheat_timer {
+                // Decrease the overheat timer
+                *timer -= time.delta_seconds();
+                if *timer <= 0.0 { *overheat_timer = None; }
+            }                
+            if *heating_progress > 0.0 && !tank.is_shooting && shooting_timer.is_none() {
+                *heating_progress -= time.delta_seconds() / beamer_config.heat_time;
             }
         }
         for (entity, body) in bodies_to_add.into_iter() {
@@ -0,0 +1,229 @@
+use nphysics2d as np;
+use ncollide2d as nc;
+use nalgebra as na;
+use rand::{thread_rng, Rng};
+use amethyst::{
+    core::timing::Time,
+    ecs::{
+        Join, System,
+        Read, ReadStorage, WriteStorage, ReadExpect,
+        Entities, Entity
+    }
+};
+use crate::bot::Bot;
+use crate::tank::{Tank, TankState};
+use crate::physics;
+use crate::weapons::{Weapon, ricochet};
+use crate::level::Level;
+use crate::markers::DeadlyMarker;
+use crate::config::{BotConfig, TankConfig, CannonConfig};
+use crate::systems::Spawn;
+
+/// Decides what computer controlled tanks do.
+/// Bots drive through the level towards the closest enemy (or a spawn, if they only have the default weapon),
+/// shoot enemies they can see and dodge bullets flying at them
+pub struct BotSystem;
+
+impl<'s> System<'s> for BotSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'s>,
+        WriteStorage<'s, Bot>,
+        ReadStorage<'s, Tank>,
+        ReadStorage<'s, physics::Body>,
+        ReadStorage<'s, physics::Collider>,
+        ReadStorage<'s, DeadlyMarker>,
+        ReadStorage<'s, Spawn>,
+        ReadExpect<'s, physics::Physics>,
+        ReadExpect<'s, Box<dyn Level>>,
+        ReadExpect<'s, BotConfig>,
+        ReadExpect<'s, TankConfig>,
+        ReadExpect<'s, CannonConfig>,
+        Read<'s, Time>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut bots,
+            tanks,
+            bodies,
+            colliders,
+            deadly_markers,
+            spawns,
+            physics,
+            level,
+            bot_config,
+            tank_config,
+            cannon_config,
+            time,
+        ): Self::SystemData,
+    ) {
+        let mut rng = thread_rng();
+
+        // Entity, position and collider of every tank that's still alive
+        let alive_tanks: Vec<(Entity, na::Point2<f32>, np::object::DefaultColliderHandle)> = (&entities, &tanks, &bodies, &colliders).join()
+            .filter(|(_, tank, ..)| tank.state == TankState::Alive)
+            .filter_map(|(entity, _, body, collider)| {
+                let rb = physics.get_rigid_body(body.handle)?;
+                Some((entity, rb.position().translation.vector.into(), collider.handle))
+            })
+            .collect();
+        // Position and velocity of everything deadly that moves (bullets)
+        let bullets: Vec<(na::Point2<f32>, na::Vector2<f32>)> = (&deadly_markers, &bodies).join()
+            .filter_map(|(_, body)| physics.get_rigid_body(body.handle))
+            .map(|rb| (rb.position().translation.vector.into(), rb.velocity().linear))
+            .filter(|(_, velocity)| velocity.norm_squared() > 0.0)
+            .collect();
+        let spawn_positions: Vec<na::Point2<f32>> = (&spawns, &bodies).join()
+            .filter_map(|(_, body)| physics.get_rigid_body(body.handle))
+            .map(|rb| rb.position().translation.vector.into())
+            .collect();
+
+        for (entity, bot, tank, body, collider) in (&entities, &mut bots, &tanks, &bodies, &colliders).join() {
+            bot.forward = 0.0;
+            bot.side = 0.0;
+            bot.fire = false;
+            if tank.state != TankState::Alive { continue; }
+
+            let rb = match physics.get_rigid_body(body.handle) {
+                Some(rb) => rb,
+                None => continue,
+            };
+            let position: na::Point2<f32> = rb.position().translation.vector.into();
+            let facing = rb.position().rotation * na::Vector2::new(0.0, 1.0);
+            let enemies = alive_tanks.iter().filter(|(other, ..)| *other != entity);
+
+            // Look around only every once in a while, so that slower bots take longer to react
+            let mut bank_shot = false;
+            bot.reaction_timer -= time.delta_seconds();
+            if bot.reaction_timer <= 0.0 {
+                bot.reaction_timer = bot.skill.reaction_time;
+                bot.aim_offset = (rng.gen_range(-1.0, 1.0) * bot.skill.aim_error).to_radians();
+
+                // Aim at the closest enemy in sight
+                bot.target = enemies.clone()
+                    .filter(|(_, enemy_position, enemy_collider)|
+                        can_see(&physics, position, *enemy_position, collider.handle, *enemy_collider)
+                    )
+                    .min_by(|a, b| na::distance(&position, &a.1).partial_cmp(&na::distance(&position, &b.1)).unwrap())
+                    .map(|(enemy, ..)| *enemy);
+
+                // Check if a bullet shot right now would bounce into an enemy
+                if let (None, Weapon::Cannon) = (bot.target, tank.weapon) {
+                    let aim = rb.position() * na::UnitComplex::new(bot.aim_offset);
+                    let path = ricochet::bullet_path(&physics, &aim, &tank_config, &cannon_config, bot_config.bank_shot_bounces);
+                    let hit_radius = tank_config.size_x.max(tank_config.size_y) as f32 / 2.0;
+                    // Whatever tank the bullet gets to first gets hit
+                    let own_tank = (entity, position, collider.handle);
+                    for (index, segment) in path.windows(2).enumerate() {
+                        let hit = enemies.clone()
+                            // The bullet can't hit us before it bounces
+                            .chain(if index > 0 { Some(&own_tank) } else { None })
+                            .map(|(hit, tank_position, _)| (*hit, segment_distance(&segment[0], &segment[1], tank_position)))
+                            .filter(|(_, distance)| *distance < hit_radius)
+                            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+                        if let Some((hit, _)) = hit {
+                            bank_shot = hit != entity;
+                            break;
+                        }
+                    }
+                }
+
+                // Find the way to the closest enemy, or to a spawn if it's closer and we only have the default weapon
+                let mut goals: Vec<na::Point2<f32>> = enemies.clone().map(|(_, enemy_position, _)| *enemy_position).collect();
+                if tank.weapon == Weapon::Cannon {
+                    goals.extend_from_slice(&spawn_positions);
+                }
+                bot.path = goals.into_iter()
+                    .filter_map(|goal| level.find_path(position, goal))
+                    .min_by_key(|path| path.len())
+                    .unwrap_or_default();
+
+                // Get out of the way of bullets that are about to pass close to the tank
+                bot.dodge = None;
+                for (bullet_position, velocity) in bullets.iter() {
+                    let offset = bullet_position - position;
+                    // Time at which the bullet gets the closest to the tank
+                    let closest_time = -offset.dot(velocity) / velocity.norm_squared();
+                    if closest_time < 0.0 || closest_time > bot_config.dodge_time { continue; }
+                    let closest_offset = offset + velocity * closest_time;
+                    if closest_offset.norm() < bot_config.dodge_distance {
+                        // Drive away from where the bullet will pass
+                        bot.dodge = Some(if facing.dot(&closest_offset) > 0.0 { -1.0 } else { 1.0 });
+                        break;
+                    }
+                }
+            }
+
+            if let Some(direction) = bot.dodge {
+                bot.forward = direction;
+                continue;
+            }
+
+            // Turn towards the target we can see and shoot when aiming close enough
+            let target_position = bot.target
+                .and_then(|target| alive_tanks.iter().find(|(enemy, ..)| *enemy == target))
+                .map(|(_, enemy_position, _)| *enemy_position);
+            if let Some(target_position) = target_position {
+                let aim = na::UnitComplex::new(bot.aim_offset) * (target_position - position);
+                let angle = signed_angle(&facing, &aim);
+                bot.side = steer(angle, bot_config.steering);
+                bot.fire = angle.abs() < bot_config.aim_tolerance.to_radians();
+                continue;
+            }
+
+            // Follow the path, skipping waypoints we already reached
+            while bot.path.len() > 1 && na::distance(&position, &bot.path[0]) < bot_config.waypoint_radius {
+                bot.path.remove(0);
+            }
+            if let Some(waypoint) = bot.path.first() {
+                let angle = signed_angle(&facing, &(waypoint - position));
+                bot.side = steer(angle, bot_config.steering);
+                // Slow down for sharp turns, so we don't drive into walls
+                bot.forward = angle.cos().max(0.0);
+            }
+            bot.fire = bank_shot;
+        }
+    }
+}
+
+/// Checks if the first solid thing on the line between two points is the collider we're looking for
+fn can_see(
+    physics: &physics::Physics,
+    from: na::Point2<f32>,
+    to: na::Point2<f32>,
+    own_collider: np::object::DefaultColliderHandle,
+    target_collider: np::object::DefaultColliderHandle,
+) -> bool {
+    let ray = nc::query::Ray::new(from, to - from);
+    physics.geom_world.interferences_with_ray(
+        &physics.colliders,
+        &ray,
+        1.0,
+        &nc::pipeline::object::CollisionGroups::new()
+    )
+        .filter(|(handle, collider, _)| *handle != own_collider && !collider.is_sensor())
+        .min_by(|a, b| a.2.toi.partial_cmp(&b.2.toi).unwrap())
+        .map_or(false, |(handle, ..)| handle == target_collider)
+}
+
+/// Distance of a point from the line segment between two points
+fn segment_distance(start: &na::Point2<f32>, end: &na::Point2<f32>, point: &na::Point2<f32>) -> f32 {
+    let segment = end - start;
+    let length_squared = segment.norm_squared();
+    if length_squared <= 0.0 { return na::distance(start, point); }
+    let along = ((point - start).dot(&segment) / length_squared).max(0.0).min(1.0);
+    na::distance(&(start + segment * along), point)
+}
+
+/// Angle from one direction to another, positive counterclockwise
+fn signed_angle(from: &na::Vector2<f32>, to: &na::Vector2<f32>) -> f32 {
+    from.perp(to).atan2(from.dot(to))
+}
+
+/// Side input turning a tank by an angle. Positive side input turns clockwise
+fn steer(angle: f32, steering: f32) -> f32 {
+    (-angle * steering).max(-1.0).min(1.0)
+}
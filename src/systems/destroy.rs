@@ -1,14 +1,15 @@
 use nphysics2d as np;
 use ncollide2d as nc;
 use nalgebra as na;
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use rand::distributions::{Distribution, Uniform};
 
 use amethyst::{
     ecs::{
-        Entities, Join, System,
-        WriteStorage, WriteExpect, ReadExpect,
+        Entities, Entity, Join, System, World,
+        Read, Write, ReadStorage, WriteStorage, WriteExpect, ReadExpect,
     },
+    shrev::{EventChannel, ReaderId},
     renderer::{
         resources::Tint,
     },
@@ -18,16 +19,39 @@ use amethyst::{
 use crate::graphics::{ShapeRender, QuadMesh};
 use crate::tank::{Tank, TankState};
 use crate::physics;
+use crate::weapons::{Projectile, Weapon};
 use crate::markers::*;
-use crate::level::MazeLevel;
-use crate::scoreboard::Scoreboard;
+use crate::level::Level;
 use crate::systems::camshake::CameraShake;
 use crate::config::DestroyConfig;
+use crate::config::MatchConfig;
 use crate::config::PerformanceConfig;
+use crate::replay::GameRng;
 
 // TODO_F: Make it possible to explode things like walls
 
-pub struct DestroySystem;
+/// An event sent when a tank gets destroyed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TankDestroyed {
+    pub victim: Entity,
+    /// The tank that shot the victim, if we know what hit it
+    pub killer: Option<Entity>,
+    /// The weapon the victim was shot with
+    pub weapon: Option<Weapon>,
+}
+
+impl TankDestroyed {
+    /// The tank shot itself, for example with a bullet that bounced back
+    pub fn is_suicide(&self) -> bool {
+        self.killer == Some(self.victim)
+    }
+}
+
+#[derive(Default)]
+pub struct DestroySystem {
+    collision_reader: Option<ReaderId<physics::CollisionEvent>>,
+    proximity_reader: Option<ReaderId<physics::ProximityEvent>>,
+}
 
 impl<'s> System<'s> for DestroySystem {
     #[allow(clippy::type_complexity)]
@@ -46,17 +70,28 @@ impl<'s> System<'s> for DestroySystem {
         WriteStorage<'s, Transform>,
         WriteStorage<'s, TempMarker>,
         WriteStorage<'s, DeadlyMarker>,
+        ReadStorage<'s, Projectile>,
 
         // TODO_L: Make a level reset timer Resource so that we don't have to fetch the whole level
-        WriteExpect<'s, MazeLevel>,
-
-        WriteExpect<'s, Scoreboard>,
+        WriteExpect<'s, Box<dyn Level>>,
 
         WriteExpect<'s, CameraShake>,
         ReadExpect<'s, DestroyConfig>,
+        ReadExpect<'s, MatchConfig>,
         ReadExpect<'s, PerformanceConfig>,
+        WriteExpect<'s, GameRng>,
+        Read<'s, EventChannel<physics::CollisionEvent>>,
+        Read<'s, EventChannel<physics::ProximityEvent>>,
+        Write<'s, EventChannel<TankDestroyed>>,
     );
 
+    fn setup(&mut self, world: &mut World) {
+        use amethyst::ecs::SystemData;
+        Self::SystemData::setup(world);
+        self.collision_reader = Some(world.fetch_mut::<EventChannel<physics::CollisionEvent>>().register_reader());
+        self.proximity_reader = Some(world.fetch_mut::<EventChannel<physics::ProximityEvent>>().register_reader());
+    }
+
     fn run (
         &mut self,
         (
@@ -72,40 +107,43 @@ impl<'s> System<'s> for DestroySystem {
             mut transforms,
             mut temp_markers,
             deadly_markers,
+            projectiles,
             mut level,
-            mut scoreboard,
             mut cam_shake,
             destroy_config,
+            match_config,
             performance_config,
+            mut game_rng,
+            collision_events,
+            proximity_events,
+            mut destroyed_events,
         ): Self::SystemData
     ) {
         // Check for tanks colliding with entities marked with DeadlyMarker
-        // Score for the tank's team
-        // and reset the level
-        physics.maintain();
-        for (collider, _) in (&colliders, &deadly_markers).join() {
-            if let Some(interactions) = 
-                physics.geom_world.interactions_with(&physics.colliders, collider.handle, true)
-            {
-                for interaction in interactions {
-                    // interaction is (collider_handle, collider, collider1_handle, collider1, Interaction)
-                    // The first collider is the one that hit the second, so the first will be our
-                    // deadly entity (for example a bullet or a laser beam)
-                    // and the second will be a wall, a tank or something else
-                    // We don't need the deadly collider handle, we only care what it hit
-                    // let deadly_handle = interaction.0;
-                    let hit_handle = interaction.2;
-                    // Match tank to collider handle and determine it's team
-                    for (tank, tank_collider) in (&mut tanks, &colliders).join() {
-                        // We can't destroy a tank that's already destroyed
-                        // This shouldn't even happen because we deactivate the rigidbody of dead tanks,
-                        // but somehow still does. This didn't happen back when we were moving dead tanks
-                        // out of frame, but now we only deactivate and hide them, so this is (somehow) necessary.
-                        if tank.state != TankState::Alive { continue; }
-                        if hit_handle == tank_collider.handle {
-                            // We change the tank's state to 'Hit' so that the following code 
-                            // will do the explosion and stuff
-                            tank.state = TankState::Hit;
+        // and reset the level or let the tank respawn
+        // Bullets collide with tanks, beams are sensors that overlap them
+        let hits = collision_events.read(self.collision_reader.as_mut().unwrap())
+            .filter(|event| event.kind == physics::CollisionKind::Started)
+            .map(|event| (event.entity1, event.entity2, true))
+            .chain(
+                proximity_events.read(self.proximity_reader.as_mut().unwrap())
+                    .filter(|event| event.kind == physics::CollisionKind::Started)
+                    .map(|event| (event.entity1, event.entity2, false))
+            );
+        for (entity1, entity2, solid) in hits {
+            // Either of the entities can be the deadly one
+            for (deadly, hit) in [(entity1, entity2), (entity2, entity1)].iter() {
+                if !deadly_markers.contains(*deadly) { continue; }
+                if let Some(tank) = tanks.get_mut(*hit) {
+                    // We can't destroy a tank that's already destroyed
+                    if tank.state == TankState::Alive {
+                        // We change the tank's state to 'Hit' so that the following code
+                        // will do the explosion and stuff
+                        tank.state = TankState::Hit;
+                        tank.hit_by = projectiles.get(*deadly).copied();
+                        // Bullets disappear when they hit, so that their tank gets the ammo back
+                        if solid {
+                            entities.delete(*deadly).expect("Couldn't remove the bullet");
                         }
                     }
                 }
@@ -115,21 +153,27 @@ impl<'s> System<'s> for DestroySystem {
         // Explode tanks that were hit, then change their state to destroyed
         // Position, angle, velocity, color
         let mut particles = Vec::<(na::Vector2::<f32>, f32, f32, ColorKey)>::new();
+        let mut any_destroyed = false;
 
-        for (tank, body, tint) in (&mut tanks, &bodies, &mut tints).join() {
+        for (entity, tank, body, tint) in (&entities, &mut tanks, &bodies, &mut tints).join() {
             if tank.state != TankState::Hit { continue; }
-            // Tell the scoreboard the tank lost the round
-            scoreboard.report_destroyed(tank.team);
+            // Tell everyone who destroyed the tank, the ScoreSystem gives out the points
+            let hit_by = tank.hit_by.take();
+            destroyed_events.single_write(TankDestroyed {
+                victim: entity,
+                killer: hit_by.map(|projectile| projectile.owner),
+                weapon: hit_by.map(|projectile| projectile.weapon),
+            });
 
             if destroy_config.particles_enabled {
-                let mut thread_rng = thread_rng();
+                let rng = &mut game_rng.0;
 
                 // Create debris particles with random SpriteRenders and velocity vectors
                 // Use uniform distribution
                 let numbers = Uniform::new(0, 5);
                 let angles = Uniform::new(0.0_f32, 360.0_f32);
                 for _ in 0..destroy_config.tank_explosion_particle_num {
-                    let color_num = numbers.sample(&mut thread_rng);
+                    let color_num = numbers.sample(rng);
                     let color = match color_num {
                         0 => ColorKey::Walls,
                         1 => ColorKey::from(tank.team),
@@ -141,12 +185,12 @@ impl<'s> System<'s> for DestroySystem {
                     // TODO_L: Weight the angle using the direction from which the tank was hit
                     //       so that the particles fly in the opposite direction
                     // Angle at which the projectile will be thrown
-                    let angle = angles.sample(&mut thread_rng);
+                    let angle = angles.sample(rng);
                     let position = 
                         physics.get_rigid_body(body.handle).unwrap().position().translation.vector
-                        + na::Vector2::new(thread_rng.gen_range(-3.0, 3.0), thread_rng.gen_range(-3.0, 3.0));
+                        + na::Vector2::new(rng.gen_range(-3.0, 3.0), rng.gen_range(-3.0, 3.0));
 
-                    let velocity = thread_rng.gen_range(
+                    let velocity = rng.gen_range(
                         destroy_config.particle_vel_bounds.0, 
                         destroy_config.particle_vel_bounds.1
                     );
@@ -168,14 +212,26 @@ impl<'s> System<'s> for DestroySystem {
 
             // Set the tank's state to Destroyed
             tank.state = TankState::Destroyed;
+            any_destroyed = true;
 
             if destroy_config.shake_enabled {
                 // Start shaking the camera
                 cam_shake.dms.push((destroy_config.tank_explosion_shake_duration, destroy_config.tank_explosion_shake_magnitude));
             }
 
-            // Start the level reset countdown
-            level.reset_timer.replace(destroy_config.level_reset_delay);
+            if match_config.scoring.respawns() {
+                // The LevelSystem brings the tank back later
+                tank.respawn_timer.replace(match_config.respawn_time);
+            }
+        }
+
+        // Start the level reset countdown once the round is decided,
+        // without pushing back a countdown that is already running
+        if any_destroyed && !match_config.scoring.respawns() && level.reset_timer().is_none() {
+            let alive = (&tanks).join().filter(|tank| tank.state == TankState::Alive).count();
+            if alive <= 1 {
+                level.reset_timer_mut().replace(destroy_config.level_reset_delay);
+            }
         }
 
         // Create the particles
@@ -0,0 +1,230 @@
+use nphysics2d as np;
+use ncollide2d as nc;
+use nalgebra as na;
+use amethyst::{
+    core::timing::Time,
+    core::transform::Transform,
+    core::math,
+    renderer::resources::Tint,
+    ecs::{
+        Component, DenseVecStorage,
+        Join, System,
+        Read, ReadStorage, WriteStorage, WriteExpect, ReadExpect,
+        Entities, Entity, LazyUpdate
+    }
+};
+use crate::graphics::{CircleMesh, ShapeRender};
+use crate::tank::{Tank, TankState};
+use crate::physics;
+use crate::weapons::{self, FragState, Projectile, Weapon, ricochet};
+use crate::config::TankConfig;
+use crate::config::CannonConfig;
+use crate::config::FragConfig;
+use crate::config::PerformanceConfig;
+use crate::markers::*;
+use crate::systems::camshake::CameraShake;
+
+/// A Component for frag bombs waiting to burst
+pub struct FragBomb {
+    /// Time until the bomb bursts on its own
+    pub fuse_timer: f32,
+}
+
+impl Component for FragBomb {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Fires slow bombs from tanks holding a frag bomb launcher. A bomb bursts into a ring of fragments
+/// flying like cannon bullets when the player presses fire again or when its fuse runs out
+pub struct FragSystem;
+
+impl<'s> System<'s> for FragSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        ReadStorage<'s, Tank>,
+        WriteStorage<'s, FragState>,
+        WriteStorage<'s, FragBomb>,
+        WriteStorage<'s, Projectile>,
+        WriteExpect<'s, physics::Physics>,
+        WriteStorage<'s, physics::Body>,
+        WriteStorage<'s, physics::Collider>,
+
+        Read<'s, Time>,
+        Entities<'s>,
+
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, Tint>,
+        WriteStorage<'s, ShapeRender>,
+        WriteStorage<'s, DynamicColorMarker>,
+        WriteStorage<'s, TempMarker>,
+        WriteStorage<'s, DeadlyMarker>,
+
+        ReadExpect<'s, TankConfig>,
+        ReadExpect<'s, CannonConfig>,
+        ReadExpect<'s, FragConfig>,
+        ReadExpect<'s, PerformanceConfig>,
+
+        ReadExpect<'s, CircleMesh>,
+        WriteExpect<'s, CameraShake>,
+        Read<'s, LazyUpdate>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            tanks,
+            mut launchers,
+            mut bombs,
+            mut projectiles,
+            mut physics,
+            mut bodies,
+            mut colliders,
+            time,
+            entities,
+            mut transforms,
+            mut tints,
+            mut shape_renders,
+            mut dyn_color_markers,
+            mut temp_markers,
+            mut deadly_markers,
+            tank_config,
+            cannon_config,
+            frag_config,
+            performance_config,
+            circle_mesh,
+            mut cam_shake,
+            lazy,
+        ): Self::SystemData,
+    ) {
+        // Bombs that burst in this frame
+        let mut bursting: Vec<Entity> = Vec::new();
+        for (entity, bomb) in (&entities, &mut bombs).join() {
+            bomb.fuse_timer -= time.delta_seconds();
+            if bomb.fuse_timer <= 0.0 {
+                bursting.push(entity);
+            }
+        }
+
+        // Entities and Bodies to be added to them because we can't borrow bodies twice in the same scope
+        let mut bodies_to_add: Vec<(Entity, physics::Body)> = Vec::new();
+        for (entity, tank, launcher, body) in (&entities, &tanks, &mut launchers, &bodies).join() {
+            // Only pressing fire counts, not holding it
+            let pressed = tank.is_shooting && !launcher.fire_held;
+            launcher.fire_held = tank.is_shooting;
+
+            // Forget bombs that burst or got removed with the level
+            if let Some(bomb) = launcher.bomb {
+                if !bombs.contains(bomb) || bursting.contains(&bomb) {
+                    launcher.bomb = None;
+                }
+            }
+
+            if pressed && tank.state == TankState::Alive {
+                if let Some(bomb) = launcher.bomb.take() {
+                    bursting.push(bomb);
+                } else if launcher.shooting_timer.is_none() {
+                    let tank_pose = *physics.get_rigid_body(body.handle).unwrap().position();
+                    let distance = (tank_config.size_y as f32 / 2.0) + frag_config.self_safety_margin;
+
+                    if performance_config.test_wallscan
+                        && weapons::barrel_blocked(&physics, &tank_pose, distance + performance_config.wallscan_toi_mod)
+                    {
+                        // The tank shot itself
+                        weapons::hit_tank(entity, Projectile { owner: entity, weapon: Weapon::Frag }, &lazy);
+                        continue;
+                    }
+
+                    let pos = na::Isometry2::new(
+                        tank_pose.translation.vector + tank_pose.rotation * na::Vector2::new(0.0, distance + frag_config.bomb_radius),
+                        tank_pose.rotation.angle(),
+                    );
+                    let vel_vec = tank_pose.rotation * na::Vector2::new(0.0, frag_config.bomb_velocity);
+                    let body = np::object::RigidBodyDesc::new()
+                        .position(pos)
+                        .velocity(np::algebra::Velocity2::linear(vel_vec.x, vel_vec.y))
+                        .build();
+                    let body_handle = physics.add_rigid_body(body);
+                    let shape = nc::shape::ShapeHandle::new(nc::shape::Ball::new(frag_config.bomb_radius));
+                    let collider = np::object::ColliderDesc::new(shape)
+                        .material(np::material::MaterialHandle::new(
+                            np::material::BasicMaterial::new(frag_config.bomb_restitution, 0.0))
+                        )
+                        .ccd_enabled(true)
+                        .density(frag_config.bomb_density)
+                        .build(np::object::BodyPartHandle(body_handle, 0));
+                    let collider_handle = physics.add_collider(collider);
+
+                    let mut transform = Transform::default();
+                    transform.set_scale(math::Vector3::new(frag_config.bomb_radius, frag_config.bomb_radius, 1.0));
+                    // Keep it off-screen until the body's position gets copied to the transform
+                    transform.set_translation_x(-200.0);
+                    // The bomb itself is harmless, only its fragments destroy tanks
+                    let ent = entities
+                        .build_entity()
+                        .with(FragBomb { fuse_timer: frag_config.fuse_time }, &mut bombs)
+                        .with(transform, &mut transforms)
+                        .with(ShapeRender { mesh: circle_mesh.handle.clone() }, &mut shape_renders)
+                        .with(Tint(Default::default()), &mut tints)
+                        .with(DynamicColorMarker(ColorKey::Text), &mut dyn_color_markers)
+                        .with(physics::Collider::new(collider_handle), &mut colliders)
+                        .with(TempMarker(None), &mut temp_markers)
+                        .with(Projectile { owner: entity, weapon: Weapon::Frag }, &mut projectiles)
+                        .build();
+                    bodies_to_add.push((ent, physics::Body::new(body_handle)));
+                    launcher.bomb = Some(ent);
+                    launcher.shooting_timer.replace(frag_config.shoot_time);
+                }
+            }
+            // Update
+            if let Some(timer) = &mut launcher.shooting_timer {
+                *timer -= time.delta_seconds();
+                if *timer <= 0.0 {
+                    launcher.shooting_timer = None;
+                }
+            }
+        }
+
+        // Burst the bombs into a ring of fragments
+        for bomb in bursting {
+            let position = bodies.get(bomb).and_then(|body| physics.get_rigid_body(body.handle));
+            // Fragments belong to whoever shot the bomb
+            let (center, projectile) = match (position, projectiles.get(bomb).copied()) {
+                (Some(rb), Some(projectile)) => (rb.position().translation.vector, projectile),
+                _ => continue,
+            };
+            entities.delete(bomb).expect("Couldn't remove the bomb");
+            cam_shake.dms.push((frag_config.explosion_shake_duration, frag_config.explosion_shake_magnitude));
+
+            // Fragments start where the bomb's edge was, far enough apart not to start inside each other
+            let radius = frag_config.bomb_radius + cannon_config.bullet_radius;
+            for i in 0..frag_config.fragment_count {
+                let angle = std::f32::consts::PI * 2.0 * i as f32 / frag_config.fragment_count as f32;
+                let rotation = na::UnitComplex::new(angle);
+                let pos = na::Isometry2::new(center + rotation * na::Vector2::new(0.0, radius), angle);
+                let (body_handle, collider_handle) = ricochet::add_bullet(&mut physics, pos, ricochet::BulletDesc::from(&*cannon_config));
+
+                let mut transform = Transform::default();
+                transform.set_scale(math::Vector3::new(
+                    cannon_config.bullet_radius, cannon_config.bullet_radius, 1.0
+                ));
+                transform.set_translation_x(-200.0);
+                let ent = entities
+                    .build_entity()
+                    .with(transform, &mut transforms)
+                    .with(ShapeRender { mesh: circle_mesh.handle.clone() }, &mut shape_renders)
+                    .with(Tint(Default::default()), &mut tints)
+                    .with(DynamicColorMarker(ColorKey::Text), &mut dyn_color_markers)
+                    .with(physics::Collider::new(collider_handle), &mut colliders)
+                    .with(TempMarker(Some(frag_config.fragment_time)), &mut temp_markers)
+                    .with(DeadlyMarker, &mut deadly_markers)
+                    .with(projectile, &mut projectiles)
+                    .build();
+                bodies_to_add.push((ent, physics::Body::new(body_handle)));
+            }
+        }
+
+        for (entity, body) in bodies_to_add.into_iter() {
+            bodies.insert(entity, body).expect("Something went wrong when adding bodies to entities");
+        }
+    }
+}
@@ -1,159 +1,198 @@
 use nphysics2d as np;
 use nalgebra as na;
 use amethyst::{
-    core::Transform,
-    renderer::{
-        resources::Tint,
-    },
     ecs::{
-        System, Entities, Join,
-        WriteStorage, Read, ReadExpect, WriteExpect
+        System, Join,
+        ReadStorage, WriteStorage, Read, WriteExpect,
+        Entity, LazyUpdate,
     },
-    window::ScreenDimensions,
     core::timing::Time,
+    renderer::resources::Tint,
     ui::UiText,
 };
-use crate::level::MazeLevel;
-use crate::tank::{Tank, TankState};
-use crate::markers::*;
-use crate::physics;
-use crate::config::MazeConfig;
+use crate::level::{Level, LevelBuildData};
+use crate::tank::{Tank, TankState, Team};
 use crate::scoreboard::Scoreboard;
-use crate::weapons::Weapon;
-use crate::graphics::{ShapeRender, QuadMesh};
+use crate::weapons::{Weapon, Projectile};
+use crate::physics;
 
 pub struct LevelSystem;
 
 impl<'s> System<'s> for LevelSystem {
     #[allow(clippy::type_complexity)]
     type SystemData = (
-        ReadExpect<'s,  MazeConfig>,
-        WriteExpect<'s, MazeLevel>,
-        Entities<'s>,
-        ReadExpect<'s, QuadMesh>,
-        WriteStorage<'s, ShapeRender>,
-        WriteStorage<'s, DynamicColorMarker>,
-        WriteStorage<'s, Tint>,
-        WriteStorage<'s, Transform>,
-        WriteExpect<'s, physics::Physics>,
-        WriteStorage<'s, physics::Body>,
-        WriteStorage<'s, physics::Collider>,
-        WriteStorage<'s, TempMarker>,
+        WriteExpect<'s, Box<dyn Level>>,
+        // Everything the level needs to rebuild itself, also used for resetting other entities
+        LevelBuildData<'s>,
         WriteStorage<'s, Tank>,
-        ReadExpect<'s, ScreenDimensions>,
+        ReadStorage<'s, Projectile>,
         Read<'s, Time>,
 
         WriteExpect<'s, Scoreboard>,
         WriteStorage<'s, UiText>,
+        Read<'s, LazyUpdate>,
     );
 
     fn run(
         &mut self,
         (
-            maze_config,
             mut level,
-            entities,
-            quad_mesh,
-            mut shape_renders,
-            mut dyn_color_markers,
-            mut tints,
-            mut transforms,
-            mut physics,
-            mut bodies,
-            mut colliders,
-            mut temp_markers,
+            mut build_data,
             mut tanks,
-            screen_dimensions,
+            projectiles,
             time,
             mut scoreboard,
             mut ui_text,
+            lazy,
         ): Self::SystemData,
     ) {
-        // Remove entities with a TempMarker Component (like projectiles)
-        // whose timer ran out, count down timers
-        for (entity, temp_marker) in (&entities, &mut temp_markers).join() {
-            if let Some(ref mut timer) = temp_marker.0 {
-                *timer -= time.delta_seconds();
-                if *timer <= 0.0 {
-                    // Remove the body and collider
-                    if let Some(body) = bodies.get(entity) {
-                        physics.remove_rigid_body(body.handle);
-                    }
-                    if let Some(collider) = colliders.get(entity) {
-                        physics.remove_collider(collider.handle);
+        {
+            let (
+                ref entities, _, _,
+                ref mut tints, _,
+                ref transforms,
+                ref mut physics,
+                ref mut bodies,
+                _,
+                ref mut temp_markers,
+                _,
+            ) = build_data;
+
+            // Remove entities with a TempMarker Component (like projectiles)
+            // whose timer ran out, count down timers
+            for (entity, temp_marker) in (entities, &mut *temp_markers).join() {
+                if let Some(ref mut timer) = temp_marker.0 {
+                    *timer -= time.delta_seconds();
+                    if *timer <= 0.0 {
+                        // Delete the entity, the PhysicsEntitySystem removes its body and collider
+                        entities.delete(entity).expect("Couldn't remove the entity");
                     }
-                    // Delete the entity
-                    entities.delete(entity).expect("Couldn't remove the entity");
                 }
             }
-        }
-        if let Some(ref mut timer) = level.reset_timer {
-            *timer -= time.delta_seconds();
-
-            if *timer <= 0.0 {
 
-                // Update score for the winners
-                scoreboard.update_winners(&mut ui_text);
+            // Bring back destroyed tanks when they are done waiting, unless the round is over anyway
+            if level.reset_timer().is_none() {
+                // Stay away from whatever could destroy the tank right after it comes back
+                let point = |entity: Entity| transforms.get(entity)
+                    .map(|transform| na::Point2::new(transform.translation().x, transform.translation().y));
+                let dangers: Vec<na::Point2<f32>> = (entities, &projectiles).join()
+                    .filter_map(|(entity, _)| point(entity))
+                    .collect();
+                let mut alive_tanks: Vec<(Team, na::Point2<f32>)> = (entities, &tanks).join()
+                    .filter(|(_, tank)| tank.state == TankState::Alive)
+                    .filter_map(|(entity, tank)| Some((tank.team, point(entity)?)))
+                    .collect();
 
-                // Reset the level
-                level.reset_timer = None;
-
-                // Remove all entities with a TempMarker Component (like projectiles)
-                for (entity, _) in (&entities, &mut temp_markers).join() {
-                    // Remove bodies and colliders belonging to entities with a TempMarker Component
-                    if let Some(body) = bodies.get(entity) {
-                        physics.remove_rigid_body(body.handle);
-                    }
-                    if let Some(collider) = colliders.get(entity) {
-                        physics.remove_collider(collider.handle);
+                for (entity, tank, body, tint) in (entities, &mut tanks, &*bodies, &mut *tints).join() {
+                    if let Some(timer) = &mut tank.respawn_timer {
+                        *timer -= time.delta_seconds();
                     }
-                    entities.delete(entity).expect("Couldn't remove the entity");
+                    if tank.respawn_timer.map_or(true, |timer| timer > 0.0) { continue; }
+
+                    revive_tank(entity, tank, body, tint, physics, &lazy);
+                    let enemies: Vec<na::Point2<f32>> = alive_tanks.iter()
+                        .filter(|(team, _)| *team != tank.team)
+                        .map(|(_, position)| *position)
+                        .collect();
+                    let position = respawn_position(&**level, &dangers, &enemies);
+                    alive_tanks.push((tank.team, position));
+                    physics.teleport_rigid_body(body.handle, na::Isometry2::new(
+                        na::Vector2::new(position.x, position.y),
+                        0.0
+                    ));
                 }
+            }
 
-                // Reset the weapons and tanks
-                for (tank, body, tint) in (&mut tanks, &bodies, &mut tints).join() {
-                    // Re-enable physics bodies of destroyed tanks
-                    let rb = physics.get_rigid_body_mut(body.handle).unwrap();
-                    if tank.state == TankState::Destroyed {
-                        use np::object::Body;
-                        rb.set_status(np::object::BodyStatus::Dynamic);
-                    }
-                    // Reset the velocity (this resets both angular and linear velocities)
-                    rb.set_velocity(np::algebra::Velocity2::zero());
+            match level.reset_timer_mut() {
+                Some(timer) => {
+                    *timer -= time.delta_seconds();
+                    if *timer > 0.0 { return; }
+                },
+                None => return,
+            }
 
-                    // Show the tank's sprite
-                    tint.0.alpha = 1.0;
+            // Update score for the winners
+            scoreboard.update_winners();
+            scoreboard.update_texts(&mut ui_text);
 
-                    tank.weapon = Weapon::default();
-                    tank.state = TankState::Alive;
-                }
+            // Reset the level
+            level.reset_timer_mut().take();
 
-                level.rebuild(
-                    &maze_config,
-                    &entities, 
-                    &quad_mesh,
-                    &mut shape_renders, 
-                    &mut tints,
-                    &mut dyn_color_markers,
-                    &mut transforms,
-                    &mut physics,
-                    &mut bodies,
-                    &mut colliders,
-                    &mut temp_markers,
-                    &screen_dimensions
-                );
-
-                // Move the tanks to new starting positions
-                for (index, (_, body)) in (&tanks, &mut bodies).join().enumerate() {
-                    let body = physics.get_rigid_body_mut(body.handle).unwrap();
-                    body.set_position(na::Isometry2::new(
-                        na::Vector2::new(level.starting_positions[index].x, level.starting_positions[index].y),
-                        0.0
-                    ));
-                }
+            // Remove all entities with a TempMarker Component (like projectiles)
+            for (entity, _) in (entities, &*temp_markers).join() {
+                entities.delete(entity).expect("Couldn't remove the entity");
+            }
 
-                physics.maintain();
+            // Reset the weapons and tanks
+            for (entity, tank, body, tint) in (entities, &mut tanks, &*bodies, &mut *tints).join() {
+                revive_tank(entity, tank, body, tint, physics, &lazy);
             }
         }
+
+        level.next_round();
+        level.rebuild(&mut build_data);
+
+        let (_, _, _, _, _, _, ref mut physics, ref bodies, ..) = build_data;
+
+        // Move the tanks to new starting positions
+        for (tank, body) in (&tanks, &*bodies).join() {
+            let position = level.starting_positions()[tank.team as usize];
+            physics.teleport_rigid_body(body.handle, na::Isometry2::new(
+                na::Vector2::new(position.x, position.y),
+                0.0
+            ));
+        }
+
+        physics.maintain();
     }
 }
+
+/// Pick where a destroyed tank comes back: the starting position or spawn location
+/// farthest from the enemies, preferring ones that share no part of the level with an enemy or a projectile
+fn respawn_position(level: &dyn Level, dangers: &[na::Point2<f32>], enemies: &[na::Point2<f32>]) -> na::Point2<f32> {
+    let candidates: Vec<na::Point2<f32>> = level.starting_positions().iter()
+        .chain(level.spawn_locations().iter())
+        .copied()
+        .collect();
+    let area = |position: na::Point2<f32>| level.area_center(position);
+    let is_clear = |candidate: &na::Point2<f32>| dangers.iter()
+        .chain(enemies.iter())
+        .all(|danger| area(*danger).map_or(true, |danger_area| Some(danger_area) != area(*candidate)));
+    let distance = |candidate: &na::Point2<f32>| enemies.iter()
+        .map(|enemy| na::distance(candidate, enemy))
+        .fold(std::f32::INFINITY, f32::min);
+
+    let clear: Vec<na::Point2<f32>> = candidates.iter().copied().filter(is_clear).collect();
+    let choices = if clear.is_empty() { &candidates } else { &clear };
+    choices.iter()
+        .copied()
+        .max_by(|a, b| distance(a).partial_cmp(&distance(b)).unwrap_or(std::cmp::Ordering::Equal))
+        .expect("The level has no starting positions")
+}
+
+/// Bring a tank back to life where it is, standing still and holding the default weapon
+fn revive_tank(
+    entity: Entity,
+    tank: &mut Tank,
+    body: &physics::Body,
+    tint: &mut Tint,
+    physics: &mut physics::Physics,
+    lazy: &LazyUpdate,
+) {
+    // Re-enable physics bodies of destroyed tanks
+    let rb = physics.get_rigid_body_mut(body.handle).unwrap();
+    if tank.state == TankState::Destroyed {
+        use np::object::Body;
+        rb.set_status(np::object::BodyStatus::Dynamic);
+    }
+    // Reset the velocity (this resets both angular and linear velocities)
+    rb.set_velocity(np::algebra::Velocity2::zero());
+
+    // Show the tank's sprite
+    tint.0.alpha = 1.0;
+
+    // Also resets the weapon's state, even if the tank already has the default weapon
+    Weapon::default().equip(entity, lazy);
+    tank.state = TankState::Alive;
+    tank.respawn_timer = None;
+}
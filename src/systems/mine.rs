@@ -0,0 +1,200 @@
+use nphysics2d as np;
+use ncollide2d as nc;
+use nalgebra as na;
+use amethyst::{
+    core::timing::Time,
+    core::transform::Transform,
+    core::math,
+    renderer::resources::Tint,
+    shrev::{EventChannel, ReaderId},
+    ecs::{
+        Component, DenseVecStorage,
+        Join, System, SystemData, World,
+        Read, ReadStorage, WriteStorage, WriteExpect, ReadExpect,
+        Entities, Entity, LazyUpdate
+    }
+};
+use crate::graphics::{CircleMesh, ShapeRender};
+use crate::tank::{Tank, TankState};
+use crate::physics;
+use crate::weapons::{self, MineState, Projectile, Weapon};
+use crate::config::TankConfig;
+use crate::config::MineConfig;
+use crate::markers::*;
+use crate::systems::camshake::CameraShake;
+
+/// A Component for mines lying in the level
+pub struct Mine {
+    /// Time until the mine can go off
+    pub arming_timer: f32,
+    /// Tanks that are close enough to set the mine off
+    pub nearby: Vec<Entity>,
+}
+
+impl Component for Mine {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Drops mines behind tanks holding a mine layer and sets them off.
+/// An armed mine destroys every tank near it, including the one that dropped it
+#[derive(Default)]
+pub struct MineSystem {
+    proximity_reader: Option<ReaderId<physics::ProximityEvent>>,
+}
+
+impl<'s> System<'s> for MineSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        ReadStorage<'s, Tank>,
+        WriteStorage<'s, MineState>,
+        WriteStorage<'s, Mine>,
+        WriteStorage<'s, Projectile>,
+        WriteExpect<'s, physics::Physics>,
+        WriteStorage<'s, physics::Body>,
+        WriteStorage<'s, physics::Collider>,
+
+        Read<'s, Time>,
+        Entities<'s>,
+
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, Tint>,
+        WriteStorage<'s, ShapeRender>,
+        WriteStorage<'s, DynamicColorMarker>,
+        WriteStorage<'s, TempMarker>,
+
+        ReadExpect<'s, TankConfig>,
+        ReadExpect<'s, MineConfig>,
+
+        ReadExpect<'s, CircleMesh>,
+        WriteExpect<'s, CameraShake>,
+        Read<'s, EventChannel<physics::ProximityEvent>>,
+        Read<'s, LazyUpdate>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.proximity_reader = Some(world.fetch_mut::<EventChannel<physics::ProximityEvent>>().register_reader());
+    }
+
+    fn run(
+        &mut self,
+        (
+            tanks,
+            mut layers,
+            mut mines,
+            mut projectiles,
+            mut physics,
+            mut bodies,
+            mut colliders,
+            time,
+            entities,
+            mut transforms,
+            mut tints,
+            mut shape_renders,
+            mut dyn_color_markers,
+            mut temp_markers,
+            tank_config,
+            mine_config,
+            circle_mesh,
+            mut cam_shake,
+            proximity_events,
+            lazy,
+        ): Self::SystemData,
+    ) {
+        // Keep track of tanks near every mine, a tank might already be there when the mine gets armed
+        for event in proximity_events.read(self.proximity_reader.as_mut().unwrap()) {
+            let pairs = [(event.entity1, event.entity2), (event.entity2, event.entity1)];
+            for (mine, other) in pairs.iter() {
+                if !tanks.contains(*other) { continue; }
+                if let Some(mine) = mines.get_mut(*mine) {
+                    match event.kind {
+                        physics::CollisionKind::Started => mine.nearby.push(*other),
+                        physics::CollisionKind::Stopped => mine.nearby.retain(|tank| tank != other),
+                    }
+                }
+            }
+        }
+
+        // Set off armed mines
+        let mut exploded: Vec<Entity> = Vec::new();
+        for (entity, mine, projectile) in (&entities, &mut mines, &projectiles).join() {
+            mine.arming_timer -= time.delta_seconds();
+            if mine.arming_timer > 0.0 { continue; }
+            // Tanks that are already destroyed don't set mines off
+            let victims: Vec<Entity> = mine.nearby.iter()
+                .copied()
+                .filter(|tank| tanks.get(*tank).map_or(false, |tank| tank.state == TankState::Alive))
+                .collect();
+            if victims.is_empty() { continue; }
+            for victim in victims {
+                weapons::hit_tank(victim, *projectile, &lazy);
+            }
+            entities.delete(entity).expect("Couldn't remove the mine");
+            exploded.push(entity);
+            cam_shake.dms.push((mine_config.explosion_shake_duration, mine_config.explosion_shake_magnitude));
+        }
+
+        // Entities and Bodies to be added to them because we can't borrow bodies twice in the same scope
+        let mut bodies_to_add: Vec<(Entity, physics::Body)> = Vec::new();
+        for (entity, tank, layer, body) in (&entities, &tanks, &mut layers, &bodies).join() {
+            let shooting_timer = &mut layer.shooting_timer;
+            if tank.is_shooting && tank.state == TankState::Alive && shooting_timer.is_none() {
+                // Mines that went off this frame are still in the storage, but they don't count
+                let laid = weapons::live_projectiles(entity, Weapon::Mine, (&entities, &projectiles).join()
+                    .filter(|(mine, _)| !exploded.contains(mine))
+                    .map(|(_, projectile)| projectile));
+                if laid < mine_config.max_mines {
+                    let tank_pose = *physics.get_rigid_body(body.handle).unwrap().position();
+                    let distance = (tank_config.size_y as f32 / 2.0) + mine_config.drop_margin + mine_config.mine_radius;
+                    let pos = na::Isometry2::new(
+                        tank_pose.translation.vector + tank_pose.rotation * na::Vector2::new(0.0, -distance),
+                        0.0,
+                    );
+                    let body = np::object::RigidBodyDesc::new()
+                        .position(pos)
+                        .status(np::object::BodyStatus::Static)
+                        .build();
+                    let body_handle = physics.add_rigid_body(body);
+                    // The mine is a sensor, so that tanks drive over it. It's bigger than it looks,
+                    // so that it goes off before a tank touches it
+                    let shape = nc::shape::ShapeHandle::new(nc::shape::Ball::new(mine_config.mine_radius + mine_config.trigger_radius));
+                    let collider = np::object::ColliderDesc::new(shape)
+                        .sensor(true)
+                        .build(np::object::BodyPartHandle(body_handle, 0));
+                    let collider_handle = physics.add_collider(collider);
+
+                    let mut transform = Transform::default();
+                    transform.set_scale(math::Vector3::new(mine_config.mine_radius, mine_config.mine_radius, 1.0));
+                    // Keep it off-screen until the body's position gets copied to the transform
+                    transform.set_translation_x(-200.0);
+                    let mut tint = Tint(Default::default());
+                    tint.0.alpha = mine_config.mine_alpha;
+                    let ent = entities
+                        .build_entity()
+                        .with(Mine { arming_timer: mine_config.arming_time, nearby: Vec::new() }, &mut mines)
+                        .with(Projectile { owner: entity, weapon: Weapon::Mine }, &mut projectiles)
+                        .with(transform, &mut transforms)
+                        .with(ShapeRender { mesh: circle_mesh.handle.clone() }, &mut shape_renders)
+                        .with(tint, &mut tints)
+                        // Mines can hit anyone, so they are neutral like bullets
+                        .with(DynamicColorMarker(ColorKey::Text), &mut dyn_color_markers)
+                        .with(physics::Collider::new(collider_handle), &mut colliders)
+                        .with(TempMarker(Some(mine_config.mine_time)), &mut temp_markers)
+                        .build();
+                    bodies_to_add.push((ent, physics::Body::new(body_handle)));
+                    shooting_timer.replace(mine_config.shoot_time);
+                }
+            }
+            // Update
+            if let Some(timer) = shooting_timer {
+                *timer -= time.delta_seconds();
+                if *timer <= 0.0 {
+                    *shooting_timer = None;
+                }
+            }
+        }
+        for (entity, body) in bodies_to_add.into_iter() {
+            bodies.insert(entity, body).expect("Something went wrong when adding bodies to entities");
+        }
+    }
+}
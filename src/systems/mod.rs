@@ -1,10 +1,18 @@
 mod tank;
 mod level;
 mod destroy;
+mod score;
 mod beamer;
 mod cannon;
+mod rocket;
+mod railgun;
+mod shotgun;
+mod mine;
+mod frag;
 mod spawn;
 mod color;
+mod bot;
+mod ammo;
 
 pub mod camshake;
 
@@ -12,8 +20,16 @@ pub use level::LevelSystem;
 pub use tank::TankSystem;
 pub use beamer::BeamerSystem;
 pub use cannon::CannonSystem;
-pub use destroy::DestroySystem;
-pub use spawn::SpawnSystem;
+pub use rocket::RocketSystem;
+pub use railgun::RailgunSystem;
+pub use shotgun::ShotgunSystem;
+pub use mine::MineSystem;
+pub use frag::FragSystem;
+pub use destroy::{DestroySystem, TankDestroyed};
+pub use score::ScoreSystem;
+pub use spawn::{SpawnSystem, Spawn};
 pub use color::ColorSystem;
+pub use bot::BotSystem;
+pub use ammo::{AmmoSystem, AmmoText};
 
 pub use camshake::CameraShakeSystem;
\ No newline at end of file
@@ -0,0 +1,155 @@
+use nalgebra as na;
+use amethyst::{
+    core::timing::Time,
+    core::transform::Transform,
+    core::math,
+    renderer::resources::Tint,
+    ecs::{
+        Join, System,
+        Read, ReadStorage, WriteStorage, WriteExpect, ReadExpect,
+        Entities, LazyUpdate
+    }
+};
+use crate::graphics::{QuadMesh, ShapeRender};
+use crate::tank::{Tank, TankState};
+use crate::physics;
+use crate::weapons::{self, RailgunState, Projectile, Weapon, ricochet};
+use crate::config::TankConfig;
+use crate::config::RailgunConfig;
+use crate::config::PerformanceConfig;
+use crate::markers::*;
+use crate::systems::camshake::CameraShake;
+
+/// Charges and fires railguns. The shot is an instant ray reflecting off walls,
+/// destroying the first tank on its way. Its path stays visible for a moment
+pub struct RailgunSystem;
+
+impl<'s> System<'s> for RailgunSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        ReadStorage<'s, Tank>,
+        WriteStorage<'s, RailgunState>,
+        ReadExpect<'s, physics::Physics>,
+        ReadStorage<'s, physics::Body>,
+        ReadStorage<'s, physics::Collider>,
+
+        Read<'s, Time>,
+        Entities<'s>,
+
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, Tint>,
+        WriteStorage<'s, ShapeRender>,
+        WriteStorage<'s, DynamicColorMarker>,
+        WriteStorage<'s, TempMarker>,
+
+        ReadExpect<'s, TankConfig>,
+        ReadExpect<'s, RailgunConfig>,
+        ReadExpect<'s, PerformanceConfig>,
+
+        ReadExpect<'s, QuadMesh>,
+        WriteExpect<'s, CameraShake>,
+        Read<'s, LazyUpdate>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            tanks,
+            mut railguns,
+            physics,
+            bodies,
+            colliders,
+            time,
+            entities,
+            mut transforms,
+            mut tints,
+            mut shape_renders,
+            mut dyn_color_markers,
+            mut temp_markers,
+            tank_config,
+            railgun_config,
+            performance_config,
+            quad_mesh,
+            mut cam_shake,
+            lazy,
+        ): Self::SystemData,
+    ) {
+        // Paths of the rays fired in this frame and who fired them
+        let mut shots = Vec::new();
+        for (entity, tank, railgun, body, collider) in (&entities, &tanks, &mut railguns, &bodies, &colliders).join() {
+            if tank.state != TankState::Alive {
+                railgun.charge_timer = None;
+            } else if tank.is_shooting && railgun.charge_timer.is_none() && railgun.shooting_timer.is_none() {
+                // Start charging, the railgun fires even if the player lets go of the button
+                railgun.charge_timer.replace(railgun_config.charge_time);
+            }
+
+            let mut fire = false;
+            if let Some(timer) = &mut railgun.charge_timer {
+                *timer -= time.delta_seconds();
+                fire = *timer <= 0.0;
+            }
+            if let Some(timer) = &mut railgun.shooting_timer {
+                *timer -= time.delta_seconds();
+                if *timer <= 0.0 {
+                    railgun.shooting_timer = None;
+                }
+            }
+            if !fire { continue; }
+            railgun.charge_timer = None;
+            railgun.shooting_timer.replace(railgun_config.shoot_time);
+
+            let tank_pose = physics.get_rigid_body(body.handle).unwrap().position();
+            let distance = (tank_config.size_y as f32 / 2.0) + railgun_config.self_safety_margin;
+            if performance_config.test_wallscan
+                && weapons::barrel_blocked(&physics, tank_pose, distance + performance_config.wallscan_toi_mod)
+            {
+                // The tank shot itself
+                weapons::hit_tank(entity, Projectile { owner: entity, weapon: Weapon::Railgun }, &lazy);
+                continue;
+            }
+
+            // Only tanks that are still alive stop the ray
+            let is_tank = |handle| physics.collider_entity(handle)
+                .and_then(|hit| tanks.get(hit))
+                .map_or(false, |hit_tank| hit_tank.state == TankState::Alive);
+            let (path, hit) = ricochet::ray_path(
+                &physics,
+                tank_pose * na::Point2::new(0.0, distance),
+                tank_pose.rotation * na::Vector2::new(0.0, 1.0),
+                railgun_config.reflections,
+                railgun_config.range,
+                Some(collider.handle),
+                is_tank,
+            );
+            if let Some(hit) = hit.and_then(|handle| physics.collider_entity(handle)) {
+                weapons::hit_tank(hit, Projectile { owner: entity, weapon: Weapon::Railgun }, &lazy);
+            }
+            shots.push((path, tank.team));
+            cam_shake.dms.push((railgun_config.shake_duration, railgun_config.shake_magnitude));
+        }
+
+        // Draw every straight part of the rays
+        for (path, team) in shots {
+            for segment in path.windows(2) {
+                let offset = segment[1] - segment[0];
+                let middle = segment[0] + offset / 2.0;
+
+                let mut transform = Transform::default();
+                transform.set_translation_xyz(middle.x, middle.y, 0.2);
+                // The quad's length is along its y axis
+                transform.set_rotation_2d((-offset.x).atan2(offset.y));
+                transform.set_scale(math::Vector3::new(railgun_config.beam_width, offset.norm(), 1.0));
+
+                entities
+                    .build_entity()
+                    .with(transform, &mut transforms)
+                    .with(ShapeRender { mesh: quad_mesh.handle.clone() }, &mut shape_renders)
+                    .with(Tint(Default::default()), &mut tints)
+                    .with(DynamicColorMarker(ColorKey::from(team)), &mut dyn_color_markers)
+                    .with(TempMarker(Some(railgun_config.beam_time)), &mut temp_markers)
+                    .build();
+            }
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use nphysics2d as np;
+use ncollide2d as nc;
+use nalgebra as na;
+use amethyst::{
+    core::timing::Time,
+    core::transform::Transform,
+    core::math,
+    renderer::resources::Tint,
+    shrev::{EventChannel, ReaderId},
+    ecs::{
+        Component, DenseVecStorage,
+        Join, System, SystemData, World,
+        Read, ReadStorage, WriteStorage, WriteExpect, ReadExpect,
+        Entities, Entity, LazyUpdate
+    }
+};
+use crate::graphics::{QuadMesh, ShapeRender};
+use crate::tank::{Tank, TankState};
+use crate::physics;
+use crate::weapons::{self, RocketState, Projectile, Weapon};
+use crate::level::Level;
+use crate::config::TankConfig;
+use crate::config::RocketConfig;
+use crate::config::PerformanceConfig;
+use crate::markers::*;
+use crate::systems::camshake::CameraShake;
+
+/// A Component for rockets flying towards enemies.
+/// A rocket doesn't follow the tank that shot it, the owner of its `Projectile`
+pub struct Rocket {
+    /// Time until the rocket starts following enemies
+    pub arming_timer: f32,
+}
+
+impl Component for Rocket {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Shoots rockets from tanks holding a rocket launcher and steers them.
+/// Once armed, a rocket follows the maze's corridors to the closest enemy and explodes when it touches
+/// a tank, a wall or another projectile
+#[derive(Default)]
+pub struct RocketSystem {
+    proximity_reader: Option<ReaderId<physics::ProximityEvent>>,
+}
+
+impl<'s> System<'s> for RocketSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        ReadStorage<'s, Tank>,
+        WriteStorage<'s, RocketState>,
+        WriteStorage<'s, Rocket>,
+        WriteStorage<'s, Projectile>,
+        WriteExpect<'s, physics::Physics>,
+        WriteStorage<'s, physics::Body>,
+        WriteStorage<'s, physics::Collider>,
+
+        Read<'s, Time>,
+        Entities<'s>,
+
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, Tint>,
+        WriteStorage<'s, ShapeRender>,
+        WriteStorage<'s, DynamicColorMarker>,
+        WriteStorage<'s, TempMarker>,
+        WriteStorage<'s, DeadlyMarker>,
+
+        ReadExpect<'s, TankConfig>,
+        ReadExpect<'s, RocketConfig>,
+        ReadExpect<'s, PerformanceConfig>,
+
+        ReadExpect<'s, QuadMesh>,
+        ReadExpect<'s, Box<dyn Level>>,
+        WriteExpect<'s, CameraShake>,
+        Read<'s, EventChannel<physics::ProximityEvent>>,
+        Read<'s, LazyUpdate>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.proximity_reader = Some(world.fetch_mut::<EventChannel<physics::ProximityEvent>>().register_reader());
+    }
+
+    fn run(
+        &mut self,
+        (
+            tanks,
+            mut launchers,
+            mut rockets,
+            mut projectiles,
+            mut physics,
+            mut bodies,
+            mut colliders,
+            time,
+            entities,
+            mut transforms,
+            mut tints,
+            mut shape_renders,
+            mut dyn_color_markers,
+            mut temp_markers,
+            mut deadly_markers,
+            tank_config,
+            rocket_config,
+            performance_config,
+            quad_mesh,
+            level,
+            mut cam_shake,
+            proximity_events,
+            lazy,
+        ): Self::SystemData,
+    ) {
+        // Blow up rockets that flew into something solid, the DestroySystem takes care of tanks they hit
+        let mut exploded: Vec<Entity> = Vec::new();
+        for event in proximity_events.read(self.proximity_reader.as_mut().unwrap()) {
+            if event.kind != physics::CollisionKind::Started { continue; }
+            let pairs = [(event.entity1, event.entity2, event.collider2), (event.entity2, event.entity1, event.collider1)];
+            for (rocket, other, other_collider) in pairs.iter() {
+                if !rockets.contains(*rocket) || exploded.contains(rocket) { continue; }
+                // Rockets explode on tanks and walls, flying through spawns, debris and other projectiles is fine
+                let solid = tanks.contains(*other) || is_wall(&physics, *other_collider);
+                if solid {
+                    entities.delete(*rocket).expect("Couldn't remove the rocket");
+                    exploded.push(*rocket);
+                    cam_shake.dms.push((rocket_config.explosion_shake_duration, rocket_config.explosion_shake_magnitude));
+                }
+            }
+        }
+
+        // Positions of tanks the rockets can fly to
+        let targets: Vec<(Entity, na::Point2<f32>)> = (&entities, &tanks, &bodies).join()
+            .filter(|(_, tank, _)| tank.state == TankState::Alive)
+            .filter_map(|(entity, _, body)| {
+                Some((entity, physics.get_rigid_body(body.handle)?.position().translation.vector.into()))
+            })
+            .collect();
+
+        // Steer the rockets
+        let max_turn = rocket_config.turn_speed.to_radians() * time.delta_seconds();
+        for (entity, rocket, body, projectile) in (&entities, &mut rockets, &bodies, &projectiles).join() {
+            if exploded.contains(&entity) { continue; }
+            let rb = match physics.get_rigid_body_mut(body.handle) {
+                Some(rb) => rb,
+                None => continue,
+            };
+            let position: na::Point2<f32> = rb.position().translation.vector.into();
+            let mut rotation = rb.position().rotation;
+
+            rocket.arming_timer -= time.delta_seconds();
+            if rocket.arming_timer <= 0.0 {
+                // Follow the shortest way through the maze to any enemy
+                let path = targets.iter()
+                    .filter(|(target, _)| *target != projectile.owner)
+                    .filter_map(|(_, target_position)| level.find_path(position, *target_position))
+                    .min_by_key(|path| path.len());
+                if let Some(path) = path {
+                    // Get to the middle of the corridor first, so that the rocket doesn't cut corners into walls.
+                    // When the enemy is in the same part of the level, fly straight at it
+                    let waypoint = match level.area_center(position) {
+                        Some(center) if path.len() > 1 && na::distance(&position, &center) > rocket_config.waypoint_radius => center,
+                        _ => path[0],
+                    };
+                    let facing = rotation * na::Vector2::new(0.0, 1.0);
+                    let wanted = waypoint - position;
+                    if wanted.norm_squared() > 0.0 {
+                        let angle = facing.perp(&wanted).atan2(facing.dot(&wanted));
+                        rotation = na::UnitComplex::new(angle.max(-max_turn).min(max_turn)) * rotation;
+                    }
+                }
+            }
+
+            // Rockets always fly forward at the same speed
+            let velocity = rotation * na::Vector2::new(0.0, rocket_config.rocket_velocity);
+            let translation = rb.position().translation;
+            rb.set_position(na::Isometry2::from_parts(translation, rotation));
+            rb.set_velocity(np::algebra::Velocity2::linear(velocity.x, velocity.y));
+        }
+
+        // Entities and Bodies to be added to them because we can't borrow bodies twice in the same scope
+        let mut bodies_to_add: Vec<(Entity, physics::Body)> = Vec::new();
+        for (entity, tank, launcher, body) in (&entities, &tanks, &mut launchers, &bodies).join() {
+            let shooting_timer = &mut launcher.shooting_timer;
+            // The player is holding the shoot button, isn't destroyed and the launcher is ready
+            // Rockets that exploded in this frame are still in the storage, but they don't count
+            let flying = weapons::live_projectiles(entity, Weapon::Rocket, (&entities, &projectiles).join()
+                .filter(|(rocket, _)| !exploded.contains(rocket))
+                .map(|(_, projectile)| projectile));
+            if tank.is_shooting && tank.state == TankState::Alive && shooting_timer.is_none() && flying < rocket_config.max_rockets {
+                let tank_pose = *physics.get_rigid_body(body.handle).unwrap().position();
+                // The rocket appears with its back at the safety margin
+                let distance = (tank_config.size_y as f32 / 2.0) + rocket_config.self_safety_margin;
+
+                if performance_config.test_wallscan
+                    && weapons::barrel_blocked(&physics, &tank_pose, distance + performance_config.wallscan_toi_mod)
+                {
+                    // The tank shot itself
+                    weapons::hit_tank(entity, Projectile { owner: entity, weapon: Weapon::Rocket }, &lazy);
+                    continue;
+                }
+
+                let pos = na::Isometry2::new(
+                    tank_pose.translation.vector + tank_pose.rotation * na::Vector2::new(0.0, distance + rocket_config.rocket_height / 2.0),
+                    tank_pose.rotation.angle(),
+                );
+                let vel_vec = tank_pose.rotation * na::Vector2::new(0.0, rocket_config.rocket_velocity);
+                let body = np::object::RigidBodyDesc::new()
+                    .position(pos)
+                    .velocity(np::algebra::Velocity2::linear(vel_vec.x, vel_vec.y))
+                    .build();
+                let body_handle = physics.add_rigid_body(body);
+                // The rocket is a sensor, so that it doesn't bounce off of things, but explodes
+                let shape = nc::shape::ShapeHandle::new(nc::shape::Cuboid::new(
+                    na::Vector2::new(rocket_config.rocket_width / 2.0, rocket_config.rocket_height / 2.0)
+                ));
+                let collider = np::object::ColliderDesc::new(shape)
+                    .sensor(true)
+                    .build(np::object::BodyPartHandle(body_handle, 0));
+                let collider_handle = physics.add_collider(collider);
+
+                let mut transform = Transform::default();
+                transform.set_scale(math::Vector3::new(rocket_config.rocket_width, rocket_config.rocket_height, 1.0));
+                // Keep it off-screen until the body's position gets copied to the transform
+                transform.set_translation_x(-200.0);
+                let ent = entities
+                    .build_entity()
+                    .with(Rocket { arming_timer: rocket_config.arming_time }, &mut rockets)
+                    .with(Projectile { owner: entity, weapon: Weapon::Rocket }, &mut projectiles)
+                    .with(transform, &mut transforms)
+                    .with(ShapeRender { mesh: quad_mesh.handle.clone() }, &mut shape_renders)
+                    .with(Tint(Default::default()), &mut tints)
+                    // Rockets can hit anyone, so they are neutral like bullets
+                    .with(DynamicColorMarker(ColorKey::Text), &mut dyn_color_markers)
+                    .with(physics::Collider::new(collider_handle), &mut colliders)
+                    .with(TempMarker(Some(rocket_config.rocket_time)), &mut temp_markers)
+                    .with(DeadlyMarker, &mut deadly_markers)
+                    .build();
+                bodies_to_add.push((ent, physics::Body::new(body_handle)));
+                // Start the shooting timer
+                shooting_timer.replace(rocket_config.shoot_time);
+            }
+            // Update
+            if let Some(timer) = shooting_timer {
+                *timer -= time.delta_seconds();
+                if *timer <= 0.0 {
+                    *shooting_timer = None;
+                }
+            }
+        }
+        for (entity, body) in bodies_to_add.into_iter() {
+            bodies.insert(entity, body).expect("Something went wrong when adding bodies to entities");
+        }
+    }
+}
+
+/// Checks if a collider belongs to a wall, which are the only static things
+fn is_wall(physics: &physics::Physics, handle: np::object::DefaultColliderHandle) -> bool {
+    use np::object::Body;
+    physics.get_collider(handle)
+        .filter(|collider| !collider.is_sensor())
+        .and_then(|collider| physics.get_rigid_body(collider.body()))
+        .map_or(false, |rb| rb.status() == np::object::BodyStatus::Static)
+}
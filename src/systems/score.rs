@@ -0,0 +1,68 @@
+use amethyst::{
+    ecs::{System, SystemData, World, Read, ReadStorage, WriteStorage, WriteExpect, ReadExpect},
+    shrev::{EventChannel, ReaderId},
+    ui::UiText,
+};
+use crate::tank::Tank;
+use crate::level::Level;
+use crate::scoreboard::Scoreboard;
+use crate::config::DestroyConfig;
+use crate::systems::TankDestroyed;
+
+/// Gives out points for destroyed tanks and ends the round when a player has enough of them
+#[derive(Default)]
+pub struct ScoreSystem {
+    destroyed_reader: Option<ReaderId<TankDestroyed>>,
+}
+
+impl<'s> System<'s> for ScoreSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        ReadStorage<'s, Tank>,
+        WriteExpect<'s, Scoreboard>,
+        WriteStorage<'s, UiText>,
+        WriteExpect<'s, Box<dyn Level>>,
+        ReadExpect<'s, DestroyConfig>,
+        Read<'s, EventChannel<TankDestroyed>>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.destroyed_reader = Some(world.fetch_mut::<EventChannel<TankDestroyed>>().register_reader());
+    }
+
+    fn run(
+        &mut self,
+        (
+            tanks,
+            mut scoreboard,
+            mut ui_text,
+            mut level,
+            destroy_config,
+            destroyed_events,
+        ): Self::SystemData,
+    ) {
+        let mut changed = false;
+        for event in destroyed_events.read(self.destroyed_reader.as_mut().unwrap()) {
+            let victim = match tanks.get(event.victim) {
+                Some(tank) => tank.team,
+                None => continue,
+            };
+            let killer = event.killer.and_then(|killer| tanks.get(killer)).map(|tank| tank.team);
+            match (killer, event.weapon) {
+                _ if event.is_suicide() => log::info!("{} destroyed itself", victim.name()),
+                (Some(killer), Some(weapon)) => log::info!("{} destroyed {} with {:?}", killer.name(), victim.name(), weapon),
+                _ => log::info!("{} got destroyed", victim.name()),
+            }
+            scoreboard.report_destroyed(victim, killer);
+            changed = true;
+        }
+        if !changed { return; }
+        scoreboard.update_texts(&mut ui_text);
+
+        // Somebody has enough kills, start the level reset countdown
+        if scoreboard.round_winner().is_some() && level.reset_timer().is_none() {
+            level.reset_timer_mut().replace(destroy_config.level_reset_delay);
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use nalgebra as na;
+use amethyst::{
+    core::timing::Time,
+    core::transform::Transform,
+    core::math,
+    renderer::resources::Tint,
+    ecs::{
+        Join, System,
+        Read, ReadStorage, WriteStorage, WriteExpect, ReadExpect,
+        Entities, Entity, LazyUpdate
+    }
+};
+use crate::graphics::{CircleMesh, ShapeRender};
+use crate::tank::{Tank, TankState};
+use crate::physics;
+use crate::weapons::{self, ShotgunState, Projectile, Weapon, ricochet};
+use crate::config::TankConfig;
+use crate::config::ShotgunConfig;
+use crate::config::PerformanceConfig;
+use crate::markers::*;
+
+/// Fires shotguns. A shot is a cone of fast pellets that disappear soon,
+/// so unlike the cannon the shotgun is only dangerous up close
+pub struct ShotgunSystem;
+
+impl<'s> System<'s> for ShotgunSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        ReadStorage<'s, Tank>,
+        WriteStorage<'s, ShotgunState>,
+        WriteStorage<'s, Projectile>,
+        WriteExpect<'s, physics::Physics>,
+        WriteStorage<'s, physics::Body>,
+        WriteStorage<'s, physics::Collider>,
+
+        Read<'s, Time>,
+        Entities<'s>,
+
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, Tint>,
+        WriteStorage<'s, ShapeRender>,
+        WriteStorage<'s, DynamicColorMarker>,
+        WriteStorage<'s, TempMarker>,
+        WriteStorage<'s, DeadlyMarker>,
+
+        ReadExpect<'s, TankConfig>,
+        ReadExpect<'s, ShotgunConfig>,
+        ReadExpect<'s, PerformanceConfig>,
+
+        ReadExpect<'s, CircleMesh>,
+        Read<'s, LazyUpdate>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            tanks,
+            mut shotguns,
+            mut projectiles,
+            mut physics,
+            mut bodies,
+            mut colliders,
+            time,
+            entities,
+            mut transforms,
+            mut tints,
+            mut shape_renders,
+            mut dyn_color_markers,
+            mut temp_markers,
+            mut deadly_markers,
+            tank_config,
+            shotgun_config,
+            performance_config,
+            circle_mesh,
+            lazy,
+        ): Self::SystemData,
+    ) {
+        // Entities and Bodies to be added to them because we can't borrow bodies twice in the same scope
+        let mut bodies_to_add: Vec<(Entity, physics::Body)> = Vec::new();
+        for (entity, tank, shotgun, body) in (&entities, &tanks, &mut shotguns, &bodies).join() {
+            let shooting_timer = &mut shotgun.shooting_timer;
+            // A shot needs room for all of its pellets
+            let has_ammo = weapons::live_projectiles(entity, Weapon::Shotgun, (&projectiles).join()) + shotgun_config.pellet_count as usize
+                <= shotgun_config.max_pellets;
+            if tank.is_shooting && tank.state == TankState::Alive && shooting_timer.is_none() && has_ammo {
+                let tank_pose = *physics.get_rigid_body(body.handle).unwrap().position();
+                let distance = (tank_config.size_y as f32 / 2.0) + shotgun_config.self_safety_margin;
+
+                if performance_config.test_wallscan
+                    && weapons::barrel_blocked(&physics, &tank_pose, distance + performance_config.wallscan_toi_mod)
+                {
+                    // The tank shot itself
+                    weapons::hit_tank(entity, Projectile { owner: entity, weapon: Weapon::Shotgun }, &lazy);
+                    continue;
+                }
+
+                let spread = shotgun_config.spread.to_radians();
+                for i in 0..shotgun_config.pellet_count {
+                    // Spread the pellets evenly across the cone, a single pellet flies straight
+                    let angle = if shotgun_config.pellet_count > 1 {
+                        spread * (i as f32 / (shotgun_config.pellet_count - 1) as f32 - 0.5)
+                    } else {
+                        0.0
+                    };
+                    let rotation = tank_pose.rotation * na::UnitComplex::new(angle);
+                    // Pellets start on an arc around the tank's origin, so that they don't start inside each other
+                    let pos = na::Isometry2::new(
+                        tank_pose.translation.vector + rotation * na::Vector2::new(0.0, distance),
+                        rotation.angle(),
+                    );
+                    let (body_handle, collider_handle) = ricochet::add_bullet(&mut physics, pos, ricochet::BulletDesc::from(&*shotgun_config));
+
+                    let mut transform = Transform::default();
+                    transform.set_scale(math::Vector3::new(
+                        shotgun_config.pellet_radius, shotgun_config.pellet_radius, 1.0
+                    ));
+                    let ent = entities
+                        .build_entity()
+                        .with(transform, &mut transforms)
+                        .with(ShapeRender { mesh: circle_mesh.handle.clone() }, &mut shape_renders)
+                        .with(Tint(Default::default()), &mut tints)
+                        .with(DynamicColorMarker(ColorKey::Text), &mut dyn_color_markers)
+                        .with(physics::Collider::new(collider_handle), &mut colliders)
+                        .with(TempMarker(Some(shotgun_config.pellet_time)), &mut temp_markers)
+                        .with(DeadlyMarker, &mut deadly_markers)
+                        .with(Projectile { owner: entity, weapon: Weapon::Shotgun }, &mut projectiles)
+                        .build();
+                    bodies_to_add.push((ent, physics::Body::new(body_handle)));
+                }
+                shooting_timer.replace(shotgun_config.shoot_time);
+            }
+            // Update
+            if let Some(timer) = shooting_timer {
+                *timer -= time.delta_seconds();
+                if *timer <= 0.0 {
+                    *shooting_timer = None;
+                }
+            }
+        }
+        for (entity, body) in bodies_to_add.into_iter() {
+            bodies.insert(entity, body).expect("Something went wrong when adding bodies to entities");
+        }
+    }
+}
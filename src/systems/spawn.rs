@@ -2,24 +2,26 @@ use nphysics2d as np;
 use nalgebra as na;
 use ncollide2d as nc;
 
-use rand::{thread_rng, Rng};
+use rand::Rng;
+use rand::seq::SliceRandom;
 use rand::distributions::{Distribution, Uniform};
 
 use amethyst::{
     core::Transform,
     renderer::SpriteRender,
     renderer::resources::Tint,
-    window::ScreenDimensions,
     core::timing::Time,
+    shrev::{EventChannel, ReaderId},
 };
 use amethyst::ecs::prelude::*;
-use crate::level::MazeLevel;
+use crate::level::Level;
 use crate::tank::Tank;
 use crate::markers::*;
 use crate::utils::SpawnsSpriteSheet;
 use crate::physics;
 use crate::weapons::Weapon;
-use crate::config::{MazeConfig, SpawnConfig};
+use crate::config::SpawnConfig;
+use crate::replay::GameRng;
 
 pub enum SpawnType {
     Weapon( Weapon ),
@@ -37,7 +39,7 @@ pub struct SpawnSystem {
     spawn_timer: f32,
     spawns_alive: u16,
     spawn_distr: Option<Uniform<u32>>,
-    taken_spawnpoints: Vec<(usize, usize)>,
+    proximity_reader: Option<ReaderId<physics::ProximityEvent>>,
 }
 
 impl Default for SpawnSystem {
@@ -49,7 +51,7 @@ impl Default for SpawnSystem {
             // We want to initialize it the first time someone calls run()
             // because we need the maze_config etc to actually initialize it
             spawn_distr: None,
-            taken_spawnpoints: Vec::new(),
+            proximity_reader: None,
         }
     }
 }
@@ -57,7 +59,7 @@ impl Default for SpawnSystem {
 impl<'s> System<'s> for SpawnSystem {
     #[allow(clippy::type_complexity)]
     type SystemData = (
-        ReadExpect<'s, MazeLevel>,
+        ReadExpect<'s, Box<dyn Level>>,
         Entities<'s>,
         ReadExpect<'s, SpawnsSpriteSheet>,
         
@@ -69,16 +71,21 @@ impl<'s> System<'s> for SpawnSystem {
         WriteStorage<'s, physics::Body>,
         WriteStorage<'s, physics::Collider>,
         WriteStorage<'s, TempMarker>,
-        WriteStorage<'s, Tank>,
+        ReadStorage<'s, Tank>,
         WriteStorage<'s, Spawn>,
 
         ReadExpect<'s,  SpawnConfig>,
-        ReadExpect<'s,  MazeConfig>,
-
-        ReadExpect<'s, ScreenDimensions>,
         Read<'s, Time>,
+        WriteExpect<'s, GameRng>,
+        Read<'s, EventChannel<physics::ProximityEvent>>,
+        Read<'s, LazyUpdate>,
     );
 
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.proximity_reader = Some(world.fetch_mut::<EventChannel<physics::ProximityEvent>>().register_reader());
+    }
+
     fn run(
         &mut self,
         (
@@ -93,30 +100,27 @@ impl<'s> System<'s> for SpawnSystem {
             mut bodies,
             mut colliders,
             mut temp_markers,
-            mut tanks,
+            tanks,
             mut spawns,
             spawn_config,
-            maze_config,
-            screen_dimensions,
-            time
+            time,
+            mut game_rng,
+            proximity_events,
+            lazy,
         ): Self::SystemData,
     ) {
         // If the level is about to be reset, zero the number of spawns
         // and keep the spawn timer frozen. This won't spawn any new spawns
         // and won't remove existing ones. When the LevelSystem resets the level,
         // all existing spawns will be automatically removed (TempMarkers)
-        if level.reset_timer.is_some() {
+        if level.reset_timer().is_some() {
             self.spawn_timer = spawn_config.spawn_time;
             self.spawns_alive = 0;
         }
 
-        let mut rng = thread_rng();
+        let rng = &mut game_rng.0;
 
-        if self.spawn_distr.is_none() { self.spawn_distr.replace(Uniform::new(0, 10)); }
-        if self.taken_spawnpoints.is_empty() {
-            self.taken_spawnpoints.push((0, 0));
-            self.taken_spawnpoints.push((level.maze.width-1, level.maze.height-1));
-        }
+        if self.spawn_distr.is_none() { self.spawn_distr.replace(Uniform::new(0, 12)); }
 
         // Count down to the next spawn only if there are less spawns than MAX_SPAWNS
         // This prevents the timer from still counting down even if the system can't spawn anymore,
@@ -125,23 +129,12 @@ impl<'s> System<'s> for SpawnSystem {
         if self.spawns_alive < spawn_config.max_spawns {
             self.spawn_timer -= time.delta_seconds();
         }
-        if self. spawn_timer <= 0.0 {
+        if self.spawn_timer <= 0.0 && !level.spawn_locations().is_empty() {
             // Spawn a spawn
-            // Determine the location
-            // We want spawns to appear in the middle of cells
-            let x_shift = (screen_dimensions.width() / 2.0) - ((level.maze.width as f32 * maze_config.cell_width) / 2.0) + (maze_config.cell_width / 2.0);
-            let y_shift = (screen_dimensions.height() / 2.0) - ((level.maze.height as f32 * maze_config.cell_height) / 2.0) + (maze_config.cell_height / 2.0);
-
-            let mut x_cell = rng.gen_range(0, level.maze.width);
-            let mut y_cell = rng.gen_range(0, level.maze.height);
-
-            // Generate new spawnpoints until we find one that isn't forbidden (taken)
-            while self.taken_spawnpoints.contains(&(x_cell, y_cell)) {
-                x_cell = rng.gen_range(0, level.maze.width);
-                y_cell = rng.gen_range(0, level.maze.height);
-            }
+            // Determine the location, the level decides where spawns can appear
+            let location = *level.spawn_locations().choose(rng).unwrap();
 
-            let (spawn, num) = random_spawn(&mut rng, self.spawn_distr.unwrap());
+            let (spawn, num) = random_spawn(rng, self.spawn_distr.unwrap());
 
             let sprite_render = SpriteRender {
                 sprite_sheet: sprite_sheet.handle.clone(),
@@ -150,21 +143,11 @@ impl<'s> System<'s> for SpawnSystem {
 
             // Transform
             let mut transform = Transform::default();
-            transform.set_translation_xyz(
-                x_shift + (x_cell as f32 * maze_config.cell_width as f32),
-                y_shift + (y_cell as f32 * maze_config.cell_height as f32),
-                -0.2
-            );
+            transform.set_translation_xyz(location.x, location.y, -0.2);
 
             let mut spawn_rb_desc = np::object::RigidBodyDesc::new();
             spawn_rb_desc.set_status(np::object::BodyStatus::Static);
-            let spawn_pos = na::Isometry2::new(
-                na::Vector2::new(
-                    x_shift + (x_cell as f32 * maze_config.cell_width as f32),
-                    y_shift + (y_cell as f32 * maze_config.cell_height as f32)
-                ),
-                0.0
-            );
+            let spawn_pos = na::Isometry2::new(location.coords, 0.0);
 
             let rb = spawn_rb_desc.position(spawn_pos).build();
             let body = physics::Body::new(physics.add_rigid_body(rb));
@@ -208,35 +191,24 @@ impl<'s> System<'s> for SpawnSystem {
         // spawn to actually trigger it, but again it could be solved by also checking against the corner points
         // of a spawn.
         // We choose to use sensor colliders with static rigidbodies for more uniform code.
-        physics.maintain();
-        // TODO_VL: Add a performance setting
-        for (spawn, collider, entity) in (&spawns, &colliders, &entities).join() {
-            if let Some(interactions) =
-                physics.geom_world.interactions_with(&physics.colliders, collider.handle, false)
-            {
-                for interaction in interactions {
-                    // interaction is (collider_handle, collider, collider1_handle, collider1, Interaction)
-                    let handle0 = interaction.0;
-                    let handle1 = interaction.2;
-                    // Match tank to collider handle and change it's weapon
-                    for (tank, tank_collider) in (&mut tanks, &colliders).join() {
-                        if handle0 == tank_collider.handle || handle1 == tank_collider.handle {
-                            // Change the tank's weapon or something else depending on the spawn's type
-                            use std::mem::discriminant; // Returns a unique identifier for an enum variant
-                                                        // which lets us check if two values are the same variant
-                            #[allow(clippy::single_match)]
-                            match &spawn.s_type {
-                                SpawnType::Weapon(spawn_weapon) => {
-                                    // Pick up only if the tank doesn't already have that weapon
-                                    if !(discriminant(&tank.weapon) == discriminant(spawn_weapon)) {
-                                        tank.weapon = spawn_weapon.clone();
-                                        spawns_to_remove.push(entity);
-                                    }
-                                },
-                                _ => (),
+        for event in proximity_events.read(self.proximity_reader.as_mut().unwrap()) {
+            if event.kind != physics::CollisionKind::Started { continue; }
+            // Either of the entities can be the spawn
+            for (entity, other) in [(event.entity1, event.entity2), (event.entity2, event.entity1)].iter() {
+                // Match the spawn's sensor to a tank and change its weapon
+                if spawns_to_remove.contains(entity) { continue; }
+                if let (Some(spawn), Some(tank)) = (spawns.get(*entity), tanks.get(*other)) {
+                    // Change the tank's weapon or something else depending on the spawn's type
+                    #[allow(clippy::single_match)]
+                    match &spawn.s_type {
+                        SpawnType::Weapon(spawn_weapon) => {
+                            // Pick up only if the tank doesn't already have that weapon
+                            if tank.weapon != *spawn_weapon {
+                                spawn_weapon.equip(*other, &lazy);
+                                spawns_to_remove.push(*entity);
                             }
-                            // Remove the spawn
-                        }
+                        },
+                        _ => (),
                     }
                 }
             }
@@ -244,13 +216,6 @@ impl<'s> System<'s> for SpawnSystem {
 
         // Remove the spawns
         for entity in spawns_to_remove {
-            // Remove bodies and colliders belonging to entities with a TempMarker Component
-            if let Some(body) = bodies.get(entity) {
-                physics.remove_rigid_body(body.handle);
-            }
-            if let Some(collider) = colliders.get(entity) {
-                physics.remove_collider(collider.handle);
-            }
             entities.delete(entity).expect("Couldn't remove the entity");
         }
     }
@@ -263,7 +228,7 @@ impl<'s> System<'s> for SpawnSystem {
 /// * 1: A coresponding sprite number to use with a SpawnsSpriteSheet
 fn random_spawn<R: Rng + ?Sized, D: Distribution<u32>>(rng: &mut R, dist: D) -> (Spawn, usize) {
 
-    // We are sure that num is in range 0..10
+    // We are sure that num is in range 0..12
     let num = dist.sample(rng);
 
     let s_type = match num {
@@ -271,9 +236,13 @@ fn random_spawn<R: Rng + ?Sized, D: Distribution<u32>>(rng: &mut R, dist: D) ->
         _ => {
             let num = dist.sample(rng);
             match num {
-                0..=7 => SpawnType::Weapon(Weapon::Cannon { shooting_timer: None }),
-                // 3..=8 => SpawnType::Weapon(Weapon::Rocket { shooting_timer: None }),
-                8..=10 => SpawnType::Weapon(Weapon::Beamer { shooting_timer: None, beam: None, heating_progress: 0.0, heating_square: None, overheat_timer: None }),
+                0..=3 => SpawnType::Weapon(Weapon::Cannon),
+                4 => SpawnType::Weapon(Weapon::Shotgun),
+                5..=6 => SpawnType::Weapon(Weapon::Rocket),
+                7 => SpawnType::Weapon(Weapon::Railgun),
+                8..=9 => SpawnType::Weapon(Weapon::Beamer),
+                10 => SpawnType::Weapon(Weapon::Mine),
+                11 => SpawnType::Weapon(Weapon::Frag),
                 _ => unreachable!(),
             }
         }
@@ -287,10 +256,13 @@ fn random_spawn<R: Rng + ?Sized, D: Distribution<u32>>(rng: &mut R, dist: D) ->
     let sprite_num = match &spawn.s_type {
         SpawnType::Weapon( weapon ) => {
             match &weapon {
-                Weapon::Cannon { .. } => 0,
-                Weapon::Beamer { .. } => 1,
-                Weapon::Rocket { .. } => 2,
-                _ => 3,
+                Weapon::Cannon => 0,
+                Weapon::Beamer => 1,
+                Weapon::Rocket => 2,
+                Weapon::Railgun => 3,
+                Weapon::Shotgun => 4,
+                Weapon::Mine => 5,
+                Weapon::Frag => 6,
             }
         }
         _ => 3
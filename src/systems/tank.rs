@@ -6,15 +6,18 @@ use amethyst::{
     },
     ecs::{
         System, Join,
-        Read, ReadExpect, ReadStorage, WriteExpect, WriteStorage,
+        Read, Write, ReadExpect, ReadStorage, WriteExpect, WriteStorage,
     },
     input::{InputHandler, StringBindings},
 };
-use crate::tank::{Tank, Team, TankState};
+use crate::tank::{Tank, TankState};
 use crate::physics;
 use crate::config::TankConfig;
 use crate::config::BeamerConfig;
-use crate::weapons::Weapon;
+use crate::config::PlayersConfig;
+use crate::weapons::BeamerState;
+use crate::bot::Bot;
+use crate::replay::{TankInput, ReplayRecorder, ReplayPlayer};
 
 pub struct TankSystem;
 
@@ -28,7 +31,13 @@ impl<'s> System<'s> for TankSystem {
         WriteExpect<'s, physics::Physics>,
         Read<'s, Time>,
 
-        ReadExpect<'s, BeamerConfig>
+        ReadExpect<'s, BeamerConfig>,
+        ReadExpect<'s, PlayersConfig>,
+        ReadStorage<'s, Bot>,
+        ReadStorage<'s, BeamerState>,
+        // Only there when recording or playing a replay
+        Option<Write<'s, ReplayRecorder>>,
+        Option<Read<'s, ReplayPlayer>>,
     );
 
     fn run(
@@ -40,25 +49,37 @@ impl<'s> System<'s> for TankSystem {
             bodies,
             mut physics,
             time,
-            beamer_config
+            beamer_config,
+            players_config,
+            bots,
+            beamers,
+            recorder,
+            replay_player,
         ): Self::SystemData,
     ) {
-        for (tank, body) in (&mut tanks, &bodies).join() {
+        // Controls of every tank in this frame, for the replay
+        let mut inputs = vec![TankInput::default(); players_config.player_count];
+
+        for (tank, body, bot, beamer) in (&mut tanks, &bodies, bots.maybe(), beamers.maybe()).join() {
             // Do not control dead tanks
             if tank.state == TankState::Alive {
-                // TODO_L: Parametric input axis names and teams for any arbitrary number of players
-                let (mov_forward, mov_side, fire) = match tank.team {
-                    Team::P1 => (
-                        input.axis_value("p1_forward").expect("axis p1_forward not defined"),
-                        input.axis_value("p1_side").expect("axis p1_side not defined"),
-                        input.action_is_down("p1_fire").expect("action p1_fire not defined")
-                    ),
-                    Team::P2 => (
-                        input.axis_value("p2_forward").expect("axis p2_forward not defined"),
-                        input.axis_value("p2_side").expect("axis p2_side not defined"),
-                        input.action_is_down("p2_fire").expect("action p2_fire not defined")
-                    )
+                let tank_input = match (&replay_player, bot) {
+                    // A replay controls every tank, even bots
+                    (Some(replay_player), _) => replay_player.input(tank.team),
+                    // Bots decide what to do in the BotSystem
+                    (None, Some(bot)) => TankInput { forward: bot.forward, side: bot.side, fire: bot.fire },
+                    // The bindings were validated when loading the config
+                    (None, None) => {
+                        let bindings = &players_config.players[tank.team as usize];
+                        TankInput {
+                            forward: input.axis_value(&bindings.forward).unwrap_or(0.0),
+                            side: input.axis_value(&bindings.side).unwrap_or(0.0),
+                            fire: input.action_is_down(&bindings.fire).unwrap_or(false),
+                        }
+                    },
                 };
+                let TankInput { forward: mov_forward, side: mov_side, fire } = tank_input.clone();
+                inputs[tank.team as usize] = tank_input;
 
                 tank.is_shooting = fire;
 
@@ -66,16 +87,12 @@ impl<'s> System<'s> for TankSystem {
                 let mut lock_movement = false;
 
                 // We want the player to be unable to move when shooting a laser beam
-                if let Weapon::Beamer {
-                    ref mut overheat_timer,
-                    ref mut shooting_timer,
-                    ..
-                } = tank.weapon {
-                    if tank.is_shooting && overheat_timer.is_none() && shooting_timer.is_none() {
+                if let Some(beamer) = beamer {
+                    if tank.is_shooting && beamer.overheat_timer.is_none() && beamer.shooting_timer.is_none() {
                         // The tank is heating up the weapon
                         lock_rotation = beamer_config.lock_rotation_when_heating;
                         lock_movement = beamer_config.lock_movement_when_heating;
-                    } else if shooting_timer.is_some() {
+                    } else if beamer.shooting_timer.is_some() {
                         // The tank is shooting
                         lock_rotation = beamer_config.lock_rotation_when_shooting;
                         lock_movement = beamer_config.lock_movement_when_shooting;
@@ -84,7 +101,9 @@ impl<'s> System<'s> for TankSystem {
 
                 let rb = physics.get_rigid_body_mut(body.handle).unwrap();
 
-                // Movement rotated relative to the tank's front and scaled by delta time
+                // Movement rotated relative to the tank's front and scaled by delta time.
+                // The physics are stepped with a fixed timestep, and velocity added every frame is proportional to the frame's
+                // length, so the tank accelerates the same no matter how many frames there are between steps
                 let movement = na::Vector2::new(
                     if !lock_rotation { mov_side } else { 0.0 }, 
                     if !lock_movement { mov_forward } else { 0.0 },
@@ -102,5 +121,9 @@ impl<'s> System<'s> for TankSystem {
                 );
             }
         }
+
+        if let Some(mut recorder) = recorder {
+            recorder.record(time.delta_seconds(), inputs);
+        }
     }
 }
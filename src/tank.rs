@@ -1,12 +1,28 @@
 use amethyst::ecs::{Component, DenseVecStorage};
-use crate::weapons::Weapon;
+use crate::weapons::{Weapon, Projectile};
 
 /// An Enum representing possible teams for tanks
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
 pub enum Team {
     P1,
     P2,
-    // TODO_H: Add support for 4 tanks
+    P3,
+    P4,
+}
+
+impl Team {
+    /// Every team, in player order
+    pub const ALL: [Team; 4] = [Team::P1, Team::P2, Team::P3, Team::P4];
+
+    /// The name shown to players
+    pub fn name(self) -> &'static str {
+        match self {
+            Team::P1 => "P1",
+            Team::P2 => "P2",
+            Team::P3 => "P3",
+            Team::P4 => "P4",
+        }
+    }
 }
 
 #[derive(PartialEq, Eq, Debug)]
@@ -20,21 +36,30 @@ pub enum TankState {
     Destroyed,
 }
 
-/// A Component carrying information about a player's tank
+/// A Component carrying information about a player's tank.
+/// The state of its weapon is in the weapon's own component (like `CannonState`)
 pub struct Tank {
     pub team: Team,
+    /// The weapon the tank holds, changed with `Weapon::equip`
     pub weapon: Weapon,
     pub is_shooting: bool,
     pub state: TankState,
+    /// What hit the tank, while it's `Hit`
+    pub hit_by: Option<Projectile>,
+    /// Time until a destroyed tank comes back, when tanks respawn
+    pub respawn_timer: Option<f32>,
 }
 
 impl Tank {
-    pub fn new(team: Team, weapon: Weapon) -> Self {
+    /// A tank holding the default weapon, it also needs that weapon's component
+    pub fn new(team: Team) -> Self {
         Tank {
             team,
-            weapon,
+            weapon: Weapon::default(),
             is_shooting: false,
             state: TankState::Alive,
+            hit_by: None,
+            respawn_timer: None,
         }
     }
 }
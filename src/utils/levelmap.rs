@@ -0,0 +1,105 @@
+use serde::{Serialize, Deserialize};
+use amethyst::{
+    assets::{Handle, Asset, AssetStorage, ProcessableAsset, ProcessingState},
+    ecs::VecStorage,
+    Error
+};
+use crate::utils::mazegen::{Maze, Cell};
+
+/// A hand-authored maze loaded from `res/levels/`
+#[derive(Clone)]
+pub struct LevelMap {
+    pub walls_h: Vec<Vec<bool>>,
+    pub walls_v: Vec<Vec<bool>>,
+    /// Cells the players start in, in player order
+    pub starting_cells: Vec<Cell>,
+    /// Cells spawns can appear in
+    pub spawn_cells: Vec<Cell>,
+}
+
+impl LevelMap {
+    /// Number of cells in the map, every player needs one to start in
+    pub fn cell_count(&self) -> usize {
+        self.walls_h.first().map_or(0, Vec::len) * self.walls_v.len()
+    }
+}
+
+/// A set of all the loaded maps, so that they can be found by their file names
+pub struct LevelMapSet {
+    pub maps: Vec<(String, Handle<LevelMap>)>,
+}
+impl LevelMapSet {
+    pub fn new() -> Self {
+        Self { maps: Vec::new() }
+    }
+
+    pub fn add_map<S: Into<String>>(&mut self, name: S, map: Handle<LevelMap>) {
+        self.maps.push((name.into(), map))
+    }
+
+    /// Get a loaded map by its name, `None` if there is no such map or it failed to load
+    pub fn get<'a>(&self, name: &str, storage: &'a AssetStorage<LevelMap>) -> Option<&'a LevelMap> {
+        self.maps.iter()
+            .find(|(item_name, _)| item_name == name)
+            .and_then(|(_, handle)| storage.get(handle))
+    }
+}
+
+/// A level file as it's written in RON
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LevelMapData {
+    /// The maze's walls as ASCII art, in the format of `Maze::paint`.
+    /// Digits inside cells mark the players' starting cells, asterisks mark spawn points
+    pub layout: String,
+    /// Starting cells, overrides the digits in the layout
+    #[serde(default)]
+    pub starting_cells: Vec<Cell>,
+    /// Spawn points, overrides the asterisks in the layout
+    #[serde(default)]
+    pub spawn_cells: Vec<Cell>,
+}
+
+impl Asset for LevelMap {
+    const NAME: &'static str = "tanks::LevelMap";
+    type Data = LevelMapData;
+    type HandleStorage = VecStorage<Handle<LevelMap>>;
+}
+impl ProcessableAsset for LevelMap {
+    fn process(data: Self::Data) -> Result<ProcessingState<Self>, Error> {
+        let (maze, markers) = Maze::from_ascii(&data.layout)
+            .map_err(|e| Error::from_string(format!("Invalid level layout: {}", e)))?;
+
+        let mut starting_cells = data.starting_cells;
+        if starting_cells.is_empty() {
+            let mut numbered: Vec<(char, Cell)> = markers.iter()
+                .filter(|(marker, _)| marker.is_ascii_digit())
+                .copied()
+                .collect();
+            numbered.sort_by_key(|(marker, _)| *marker);
+            starting_cells = numbered.into_iter().map(|(_, cell)| cell).collect();
+        }
+        let mut spawn_cells = data.spawn_cells;
+        if spawn_cells.is_empty() {
+            spawn_cells = markers.iter()
+                .filter(|(marker, _)| *marker == '*')
+                .map(|(_, cell)| *cell)
+                .collect();
+        }
+
+        if starting_cells.len() < 2 {
+            return Err(Error::from_string(format!("A level needs at least 2 starting cells, found {}", starting_cells.len())));
+        }
+        if let Some(cell) = starting_cells.iter().chain(spawn_cells.iter())
+            .find(|cell| cell.col >= maze.width || cell.row >= maze.height)
+        {
+            return Err(Error::from_string(format!("Cell {:?} is outside of the level", cell)));
+        }
+
+        Ok(ProcessingState::Loaded(Self {
+            walls_h: maze.walls_h,
+            walls_v: maze.walls_v,
+            starting_cells,
+            spawn_cells,
+        }))
+    }
+}
@@ -1,13 +1,53 @@
-use rand::{thread_rng, Rng};
+use rand::{thread_rng, Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use serde::{Serialize, Deserialize};
+
+/// Algorithms the maze can be generated with
+/// Every one of them generates a maze where each cell can be reached,
+/// but they differ in how long and twisty the corridors are
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum MazeAlgorithm {
+    /// Depth-first search, long corridors with few dead ends
+    Backtracker,
+    /// Many short dead ends branching from the middle
+    Prim,
+    /// Many short dead ends spread evenly
+    Kruskal,
+    /// Uniformly random mazes
+    Wilson,
+    /// Row by row, tends to have long horizontal passages
+    Eller,
+    /// Long straight walls splitting the maze into rooms
+    RecursiveDivision,
+}
+
+impl Default for MazeAlgorithm {
+    fn default() -> Self {
+        MazeAlgorithm::Backtracker
+    }
+}
+
+/// Numbers describing a maze's layout
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MazeMetrics {
+    /// Cells that can only be left one way
+    pub dead_ends: usize,
+    /// Independent loops, 0 for a perfect maze
+    pub loops: usize,
+    /// Average number of cells between junctions or dead ends
+    pub average_corridor_length: f32,
+    pub longest_corridor: usize,
+}
  
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Cell {
     pub col: usize,
     pub row: usize,
 }
  
 impl Cell {
-    fn from(col: usize, row: usize) -> Cell {
+    pub fn from(col: usize, row: usize) -> Cell {
         Cell {col, row}
     }
 }
@@ -26,15 +66,18 @@ pub struct Maze {
     pub walls_v: Vec<Vec<bool>>,   //vertical walls existing/removed
     pub start_cell: Cell,
     pub end_cell: Cell,
-    // Thread_rng is not Send+Sync, so we couldn't use Maze as a Resource
-    // because for some reason random number generators can't be safely sent between threads
-    // thread_rng: ThreadRng,      //Random numbers generator
+    /// The seed the current layout was generated from
+    pub seed: u64,
+    // Thread_rng is not Send+Sync, so we couldn't use Maze as a Resource,
+    // but a seeded StdRng is, and it also makes every layout reproducible
+    rng: StdRng,        //Random numbers generator
 }
  
 impl Maze {
  
     /// Initializes the maze, with all the cells unvisited and all the walls active
-    pub fn new(width: usize, height: usize) -> Maze {
+    /// The same seed always builds the same maze
+    pub fn new(width: usize, height: usize, seed: u64) -> Maze {
         Maze { 
             width,
             height,
@@ -42,33 +85,52 @@ impl Maze {
             walls_h: vec![vec![true; width]; height + 1],
             walls_v: vec![vec![true; width + 1]; height],
             start_cell: Cell::default(),
-            end_cell: Cell::default()
+            end_cell: Cell::default(),
+            seed,
+            rng: StdRng::seed_from_u64(seed),
         }
     }
 
-    /// Reset the maze
+    /// Generates a random seed, for when we don't care which maze we get
+    pub fn random_seed() -> u64 {
+        thread_rng().gen()
+    }
+
+    /// Reset the maze and rewind the random number generator,
+    /// so that the next `build()` generates the same layout again
     pub fn reset(&mut self) {
         self.cells = vec![vec![true; self.height]; self.width];
         self.walls_h = vec![vec![true; self.width]; self.height + 1];
         self.walls_v = vec![vec![true; self.width + 1]; self.height];
         self.start_cell = Cell::default();
         self.end_cell = Cell::default();
+        self.rng = StdRng::seed_from_u64(self.seed);
+    }
+
+    /// Reset the maze and use a different seed for the next `build()`
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.reset();
+    }
+
+    /// Draws a seed for the next maze from this maze's generator.
+    /// Chaining this keeps a whole sequence of mazes reproducible from the first seed
+    pub fn next_seed(&mut self) -> u64 {
+        self.rng.gen()
     }
  
     /// Randomly chooses the starting cell
     fn first(&mut self) -> Cell {
-        let mut thread_rng = thread_rng();
-        Cell::from(thread_rng.gen_range(0, self.width), thread_rng.gen_range(0, self.height))
+        Cell::from(self.rng.gen_range(0, self.width), self.rng.gen_range(0, self.height))
     }
  
     /// Opens the enter and exit doors (unused, because we want our maze closed)
     #[allow(unused)]
     pub fn open_doors(&mut self) {
-        let mut thread_rng = thread_rng();
-        let from_top: bool = thread_rng.gen();
+        let from_top: bool = self.rng.gen();
         let limit = if from_top { self.width } else { self.height };
-        let door = thread_rng.gen_range(0, limit);
-        let exit = thread_rng.gen_range(0, limit);
+        let door = self.rng.gen_range(0, limit);
+        let exit = self.rng.gen_range(0, limit);
         if from_top { 
             self.walls_h[0][door] = false;
             self.walls_h[self.height][exit] = false;
@@ -78,26 +140,105 @@ impl Maze {
         }
     }
 
-    /// Removes a few internal walls randomly
-    pub fn open_random(&mut self) {
-        let mut thread_rng = thread_rng();
-        let mut amount = thread_rng.gen_range(0, ((self.width*self.height) as f32).sqrt().floor() as usize);
-        while amount > 0 {
-            let horizontal: bool = thread_rng.gen();
-            if horizontal {
-                let x = thread_rng.gen_range(0, self.width);
-                let y = thread_rng.gen_range(1, self.height);
-                if !self.walls_h[y][x] { amount += 1; } else {
-                    self.walls_h[y][x] = false;
-                }
-            } else {
-                let x = thread_rng.gen_range(1, self.width);
-                let y = thread_rng.gen_range(0, self.height);
-                if !self.walls_v[y][x] { amount += 1; } else {
-                    self.walls_v[y][x] = false;
+    /// Returns every dead end - a cell that can only be left one way
+    fn dead_ends(&self) -> Vec<Cell> {
+        let mut dead_ends = Vec::new();
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let cell = Cell::from(col, row);
+                if self.open_neighbors(&cell).len() == 1 { dead_ends.push(cell); }
+            }
+        }
+        dead_ends
+    }
+
+    /// Opens a wall of a dead end, turning it into a loop.
+    /// Prefers opening into another dead end, so that both get removed at once
+    fn braid_dead_end(&mut self, cell: &Cell) {
+        let walled: Vec<Cell> = self.adjacent(cell).into_iter()
+            .filter(|next| self.has_wall(cell, next))
+            .collect();
+        let dead_ends: Vec<Cell> = walled.iter()
+            .filter(|next| self.open_neighbors(next).len() == 1)
+            .copied()
+            .collect();
+        let candidates = if dead_ends.is_empty() { walled } else { dead_ends };
+        if let Some(next) = candidates.choose(&mut self.rng) {
+            let next = *next;
+            self.remove_wall(cell, &next);
+        }
+    }
+
+    /// Removes roughly `factor` (0.0 - 1.0) of the maze's dead ends by opening one of their walls.
+    /// Every removed dead end adds a loop, so 0.0 keeps the maze perfect and 1.0 leaves no dead ends
+    pub fn braid(&mut self, factor: f32) {
+        let mut dead_ends = self.dead_ends();
+        dead_ends.shuffle(&mut self.rng);
+        for cell in dead_ends {
+            // Braiding a neighbor could have already removed this one
+            if self.open_neighbors(&cell).len() != 1 { continue; }
+            if self.rng.gen::<f32>() < factor {
+                self.braid_dead_end(&cell);
+            }
+        }
+    }
+
+    /// Braids random dead ends until there are at most `max` of them left
+    pub fn limit_dead_ends(&mut self, max: usize) {
+        let mut dead_ends = self.dead_ends();
+        dead_ends.shuffle(&mut self.rng);
+        while dead_ends.len() > max {
+            let cell = dead_ends.pop().unwrap();
+            self.braid_dead_end(&cell);
+            dead_ends.retain(|cell| self.open_neighbors(cell).len() == 1);
+        }
+    }
+
+    /// Measures the maze's layout, for tuning the generation settings
+    pub fn metrics(&self) -> MazeMetrics {
+        let cells = self.width * self.height;
+        let mut passages = 0;
+        let mut junctions = Vec::new();
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let cell = Cell::from(col, row);
+                let degree = self.open_neighbors(&cell).len();
+                passages += degree;
+                // Corridors run between cells that aren't just a part of a corridor
+                if degree != 2 { junctions.push(cell); }
+            }
+        }
+        // Every passage was counted from both of its sides
+        passages /= 2;
+
+        // Follow every corridor from both of its ends
+        let mut corridors = 0;
+        let mut corridors_length = 0;
+        let mut longest_corridor = 0;
+        for start in junctions {
+            for first in self.open_neighbors(&start) {
+                let (mut previous, mut cell, mut length) = (start, first, 1);
+                loop {
+                    let next: Vec<Cell> = self.open_neighbors(&cell).into_iter()
+                        .filter(|next| *next != previous)
+                        .collect();
+                    if next.len() != 1 { break; }
+                    previous = cell;
+                    cell = next[0];
+                    length += 1;
                 }
+                corridors += 1;
+                corridors_length += length;
+                longest_corridor = longest_corridor.max(length);
             }
-            amount -= 1;
+        }
+
+        MazeMetrics {
+            dead_ends: self.dead_ends().len(),
+            // Every passage over a spanning tree (which has cells - 1 passages) closes a loop
+            loops: (passages + 1).saturating_sub(cells),
+            average_corridor_length: if corridors > 0 { corridors_length as f32 / corridors as f32 } else { 0.0 },
+            longest_corridor,
         }
     }
  
@@ -112,7 +253,6 @@ impl Maze {
  
     /// Returns a random non-visited neighbor of the Cell passed as argument
     fn neighbor(&mut self, cell: &Cell) -> Option<Cell> {
-        let mut thread_rng = thread_rng();
         self.cells[cell.col][cell.row] = false;
         let mut neighbors = Vec::new();
         if cell.col > 0 && self.cells[cell.col - 1][cell.row] { neighbors.push(Cell::from(cell.col - 1, cell.row)); }
@@ -122,14 +262,91 @@ impl Maze {
         if neighbors.is_empty() {
             None
         } else {
-            let next = neighbors.get(thread_rng.gen_range(0, neighbors.len())).unwrap();
+            let next = neighbors.get(self.rng.gen_range(0, neighbors.len())).unwrap();
             self.remove_wall(cell, next);
             Some(*next)
         }
     }
  
-    /// Builds the maze (runs the Depth-first search algorithm)
-    pub fn build(&mut self) {
+    /// Returns every neighbor of the Cell passed as argument that lies inside the maze
+    fn adjacent(&self, cell: &Cell) -> Vec<Cell> {
+        let mut adjacent = Vec::with_capacity(4);
+        if cell.col > 0 { adjacent.push(Cell::from(cell.col - 1, cell.row)); }
+        if cell.row > 0 { adjacent.push(Cell::from(cell.col, cell.row - 1)); }
+        if cell.col < self.width - 1 { adjacent.push(Cell::from(cell.col + 1, cell.row)); }
+        if cell.row < self.height - 1 { adjacent.push(Cell::from(cell.col, cell.row + 1)); }
+        adjacent
+    }
+
+    /// Checks if there is a wall between two neighboring cells
+    pub fn has_wall(&self, cell1: &Cell, cell2: &Cell) -> bool {
+        if cell1.row == cell2.row {
+            self.walls_v[cell1.row][if cell1.col > cell2.col { cell1.col } else { cell2.col }]
+        } else {
+            self.walls_h[if cell1.row > cell2.row { cell1.row } else { cell2.row }][cell1.col]
+        }
+    }
+
+    /// Returns the neighbors of the Cell passed as argument that can be reached without crossing a wall
+    pub fn open_neighbors(&self, cell: &Cell) -> Vec<Cell> {
+        self.adjacent(cell).into_iter()
+            .filter(|next| !self.has_wall(cell, next))
+            .collect()
+    }
+
+    /// Finds the shortest way from one cell to another, without crossing walls.
+    /// The path includes both the starting and the target cell
+    pub fn find_path(&self, from: &Cell, to: &Cell) -> Option<Vec<Cell>> {
+        if from.col >= self.width || from.row >= self.height || to.col >= self.width || to.row >= self.height {
+            return None;
+        }
+        // Breadth-first search, remembering where every cell was reached from
+        let mut came_from: Vec<Vec<Option<Cell>>> = vec![vec![None; self.height]; self.width];
+        came_from[from.col][from.row] = Some(*from);
+        let mut queue = std::collections::VecDeque::new();
+        queue.push_back(*from);
+        while let Some(cell) = queue.pop_front() {
+            if cell == *to {
+                // Walk back to the start
+                let mut path = vec![cell];
+                let mut current = cell;
+                while current != *from {
+                    current = came_from[current.col][current.row].unwrap();
+                    path.push(current);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for next in self.open_neighbors(&cell) {
+                if came_from[next.col][next.row].is_none() {
+                    came_from[next.col][next.row] = Some(cell);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    /// Builds a perfect maze (without any loops) with the chosen algorithm
+    pub fn build(&mut self, algorithm: MazeAlgorithm) {
+        match algorithm {
+            MazeAlgorithm::Backtracker => self.build_backtracker(),
+            MazeAlgorithm::Prim => self.build_prim(),
+            MazeAlgorithm::Kruskal => self.build_kruskal(),
+            MazeAlgorithm::Wilson => self.build_wilson(),
+            MazeAlgorithm::Eller => self.build_eller(),
+            MazeAlgorithm::RecursiveDivision => self.build_division(),
+        }
+        // Set the start and end cells  - the opposite corners of the maze
+        // We know that with every generation alghoritm we have
+        // every cell in the maze can be reached, so we can even choose them
+        // randomly, but choosing opposite corners is much more balanced
+        self.start_cell = Cell::from(0, 0);
+        self.end_cell = Cell::from(self.width - 1, self.height - 1);
+    }
+
+    /// Runs the Depth-first search algorithm (recursive backtracker)
+    fn build_backtracker(&mut self) {
         let mut cell_stack: Vec<Cell> = Vec::new();
         let mut next = self.first();
         loop {
@@ -142,45 +359,439 @@ impl Maze {
                 None => break
             }
         }
-        self.open_random();
-        // Set the start and end cells  - the opposite corners of the maze
-        // We know that with the depth-first search generation alghoritm
-        // every cell in the maze can be reached, so we can even choose them
-        // randomly, but choosing opposite corners is much more balanced
-        self.start_cell = Cell::from(0, 0);
-        self.end_cell = Cell::from(self.width - 1, self.height - 1);
+    }
+
+    /// Runs the randomized Prim's algorithm: the maze grows from a single cell,
+    /// connecting a random cell from its frontier every step
+    fn build_prim(&mut self) {
+        let first = self.first();
+        self.cells[first.col][first.row] = false;
+        let mut frontier = self.adjacent(&first);
+        while !frontier.is_empty() {
+            let cell = frontier.swap_remove(self.rng.gen_range(0, frontier.len()));
+            // Connect the cell to a random neighbor that is already a part of the maze
+            let visited: Vec<Cell> = self.adjacent(&cell).into_iter()
+                .filter(|next| !self.cells[next.col][next.row])
+                .collect();
+            let next = visited[self.rng.gen_range(0, visited.len())];
+            self.remove_wall(&cell, &next);
+            self.cells[cell.col][cell.row] = false;
+            // Extend the frontier with the cell's neighbors that aren't in the maze yet
+            for next in self.adjacent(&cell) {
+                if self.cells[next.col][next.row] && !frontier.contains(&next) {
+                    frontier.push(next);
+                }
+            }
+        }
+    }
+
+    /// Runs the randomized Kruskal's algorithm: walls are removed in random order
+    /// if the cells they separate aren't connected yet
+    fn build_kruskal(&mut self) {
+        // Every internal wall as a pair of the cells it separates
+        let mut walls: Vec<(Cell, Cell)> = Vec::new();
+        for row in 0..self.height {
+            for col in 0..self.width {
+                if col > 0 { walls.push((Cell::from(col - 1, row), Cell::from(col, row))); }
+                if row > 0 { walls.push((Cell::from(col, row - 1), Cell::from(col, row))); }
+            }
+        }
+        walls.shuffle(&mut self.rng);
+
+        // Disjoint sets of connected cells, indexed by `row * width + col`
+        let mut parents: Vec<usize> = (0..self.width * self.height).collect();
+        fn find(parents: &mut [usize], mut index: usize) -> usize {
+            while parents[index] != index {
+                parents[index] = parents[parents[index]];
+                index = parents[index];
+            }
+            index
+        }
+
+        for (cell1, cell2) in walls {
+            let set1 = find(&mut parents, cell1.row * self.width + cell1.col);
+            let set2 = find(&mut parents, cell2.row * self.width + cell2.col);
+            if set1 != set2 {
+                parents[set2] = set1;
+                self.remove_wall(&cell1, &cell2);
+            }
+        }
+    }
+
+    /// Runs Wilson's algorithm: loop-erased random walks from cells outside the maze
+    /// get carved in as soon as they reach it
+    fn build_wilson(&mut self) {
+        let first = self.first();
+        self.cells[first.col][first.row] = false;
+
+        let mut remaining: Vec<Cell> = Vec::new();
+        for col in 0..self.width {
+            for row in 0..self.height {
+                if self.cells[col][row] { remaining.push(Cell::from(col, row)); }
+            }
+        }
+        remaining.shuffle(&mut self.rng);
+
+        // The direction the walk last left each cell in,
+        // overwriting it erases the loops from the walk
+        let mut exits: Vec<Vec<Cell>> = vec![vec![Cell::default(); self.height]; self.width];
+        for start in remaining {
+            if !self.cells[start.col][start.row] { continue; }
+            // Walk until we hit the maze
+            let mut cell = start;
+            while self.cells[cell.col][cell.row] {
+                let adjacent = self.adjacent(&cell);
+                let next = adjacent[self.rng.gen_range(0, adjacent.len())];
+                exits[cell.col][cell.row] = next;
+                cell = next;
+            }
+            // Carve the loop-erased path into the maze
+            let mut cell = start;
+            while self.cells[cell.col][cell.row] {
+                let next = exits[cell.col][cell.row];
+                self.cells[cell.col][cell.row] = false;
+                self.remove_wall(&cell, &next);
+                cell = next;
+            }
+        }
+    }
+
+    /// Runs Eller's algorithm: the maze is built row by row, only remembering
+    /// which cells of the current row are already connected
+    fn build_eller(&mut self) {
+        // The set every cell of the current row belongs to
+        let mut sets: Vec<Option<usize>> = vec![None; self.width];
+        let mut next_set = 0;
+        for row in 0..self.height {
+            let last_row = row == self.height - 1;
+            // Cells that weren't connected from the row above start their own sets
+            for set in sets.iter_mut().filter(|set| set.is_none()) {
+                set.replace(next_set);
+                next_set += 1;
+            }
+            // Randomly join neighboring cells from different sets,
+            // in the last row every set has to be joined
+            for col in 1..self.width {
+                let (left, right) = (sets[col - 1].unwrap(), sets[col].unwrap());
+                if left != right && (last_row || self.rng.gen()) {
+                    self.remove_wall(&Cell::from(col - 1, row), &Cell::from(col, row));
+                    for set in sets.iter_mut().filter(|set| **set == Some(right)) {
+                        set.replace(left);
+                    }
+                }
+            }
+            if last_row { break; }
+            // Every set has to continue downwards at least once
+            let mut below: Vec<Option<usize>> = vec![None; self.width];
+            let mut unique = sets.clone();
+            unique.sort();
+            unique.dedup();
+            for set in unique {
+                let mut members: Vec<usize> = (0..self.width).filter(|&col| sets[col] == set).collect();
+                members.shuffle(&mut self.rng);
+                let amount = self.rng.gen_range(1, members.len() + 1);
+                for &col in members.iter().take(amount) {
+                    self.remove_wall(&Cell::from(col, row), &Cell::from(col, row + 1));
+                    below[col] = set;
+                }
+            }
+            sets = below;
+        }
+    }
+
+    /// Runs the recursive division algorithm: starting from an empty room,
+    /// walls with a single passage split it until every chamber is one cell wide
+    fn build_division(&mut self) {
+        // Remove all the internal walls, keep the outer ones
+        let (width, height) = (self.width, self.height);
+        for (row, walls) in self.walls_h.iter_mut().enumerate() {
+            if row != 0 && row != height { walls.iter_mut().for_each(|wall| *wall = false); }
+        }
+        for walls in self.walls_v.iter_mut() {
+            walls.iter_mut().enumerate()
+                .filter(|(col, _)| *col != 0 && *col != width)
+                .for_each(|(_, wall)| *wall = false);
+        }
+
+        // Chambers to divide as (col, row, width, height)
+        let mut chambers = vec![(0, 0, self.width, self.height)];
+        while let Some((col, row, width, height)) = chambers.pop() {
+            if width < 2 || height < 2 {
+                // A corridor can't be divided without cutting it off
+                continue;
+            }
+            let horizontal = if width == height { self.rng.gen() } else { height > width };
+            if horizontal {
+                // A wall above the row `row + split`, with one passage in it
+                let split = self.rng.gen_range(1, height);
+                let passage = self.rng.gen_range(col, col + width);
+                for wall_col in (col..col + width).filter(|&c| c != passage) {
+                    self.walls_h[row + split][wall_col] = true;
+                }
+                chambers.push((col, row, width, split));
+                chambers.push((col, row + split, width, height - split));
+            } else {
+                // A wall left of the column `col + split`, with one passage in it
+                let split = self.rng.gen_range(1, width);
+                let passage = self.rng.gen_range(row, row + height);
+                for wall_row in (row..row + height).filter(|&r| r != passage) {
+                    self.walls_v[wall_row][col + split] = true;
+                }
+                chambers.push((col, row, split, height));
+                chambers.push((col + split, row, width - split, height));
+            }
+        }
     }
  
-    /// Displays a wall
-    fn paint_wall(h_wall: bool, active: bool) {
-        if h_wall {
-            print!("{}", if active { "+---" } else { "+   " });
+    /// Writes a whole row of walls
+    fn paint_row(&self, layout: &mut String, h_walls: bool, index: usize) {
+        if h_walls {
+            for &wall in self.walls_h[index].iter() {
+                layout.push_str(if wall { "+---" } else { "+   " });
+            }
+            layout.push('+');
         } else {
-            print!("{}", if active { "|   " } else { "    " });
+            for (col, &wall) in self.walls_v[index].iter().enumerate() {
+                layout.push(if wall { '|' } else { ' ' });
+                // The last wall closes the row
+                if col < self.width { layout.push_str("   "); }
+            }
         }
+        layout.push('\n');
     }
- 
-    /// Displays a final wall for a row
-    fn paint_close_wall(h_wall: bool) {
-        if h_wall { println!("+") } else { println!() }
+
+    /// Writes the maze as ASCII art, in the same format `from_ascii` reads
+    pub fn to_ascii(&self) -> String {
+        let mut layout = String::new();
+        for i in 0 .. self.height {
+            self.paint_row(&mut layout, true, i);
+            self.paint_row(&mut layout, false, i);
+        }
+        self.paint_row(&mut layout, true, self.height);
+        layout
     }
  
-    /// Displays a whole row of walls
-    fn paint_row(&self, h_walls: bool, index: usize) {
-        let iter = if h_walls { self.walls_h[index].iter() } else { self.walls_v[index].iter() };
-        for &wall in iter {
-            Maze::paint_wall(h_walls, wall);
-        }
-        Maze::paint_close_wall(h_walls);
-    } 
- 
     /// Paints the maze
     #[allow(unused)]
     pub fn paint(&self) {
-        for i in 0 .. self.width {
-            self.paint_row(true, i);
-            self.paint_row(false, i);
+        print!("{}", self.to_ascii());
+    }
+
+    /// Reads a maze from ASCII art like the one `paint` prints:
+    /// ```text
+    /// +---+---+---+
+    /// | 1   *     |
+    /// +   +---+   +
+    /// |   |     2 |
+    /// +---+---+---+
+    /// ```
+    /// Every cell is 4 characters wide and every wall must be in its place, anything else
+    /// inside a cell is returned as a marker together with the cell it was found in
+    pub fn from_ascii(layout: &str) -> Result<(Maze, Vec<(char, Cell)>), String> {
+        let lines: Vec<&str> = layout.lines()
+            .map(|line| line.trim_end())
+            .filter(|line| !line.is_empty())
+            .collect();
+        if lines.len() < 3 || lines.len() % 2 == 0 {
+            return Err(format!("expected an odd number of at least 3 lines, found {}", lines.len()));
+        }
+        let width = (lines[0].len().saturating_sub(1)) / 4;
+        let height = lines.len() / 2;
+        if width == 0 || lines[0].len() != width * 4 + 1 {
+            return Err(format!("the first line is {} characters long, expected 4 per cell and one more", lines[0].len()));
+        }
+
+        let mut maze = Maze::new(width, height, 0);
+        let mut markers = Vec::new();
+        for (index, line) in lines.iter().enumerate() {
+            let chars: Vec<char> = line.chars().collect();
+            // Lines with vertical walls can have their trailing spaces trimmed
+            let char_at = |i: usize| chars.get(i).copied().unwrap_or(' ');
+            if chars.len() > width * 4 + 1 {
+                return Err(format!("line {} is too long", index + 1));
+            }
+            let row = index / 2;
+            if index % 2 == 0 {
+                for col in 0..width {
+                    if char_at(col * 4) != '+' {
+                        return Err(format!("missing '+' on line {}, column {}", index + 1, col * 4 + 1));
+                    }
+                    maze.walls_h[row][col] = match (1..4).map(|i| char_at(col * 4 + i)).collect::<String>().as_str() {
+                        "---" => true,
+                        "   " => false,
+                        other => return Err(format!("unexpected \"{}\" on line {}, expected a wall or spaces", other, index + 1)),
+                    }
+                }
+            } else {
+                for col in 0..=width {
+                    maze.walls_v[row][col] = match char_at(col * 4) {
+                        '|' => true,
+                        ' ' => false,
+                        other => return Err(format!("unexpected '{}' on line {}, expected a wall or a space", other, index + 1)),
+                    };
+                    if col < width {
+                        for marker in (1..4).map(|i| char_at(col * 4 + i)).filter(|c| *c != ' ') {
+                            markers.push((marker, Cell::from(col, row)));
+                        }
+                    }
+                }
+            }
+        }
+        maze.end_cell = Cell::from(width - 1, height - 1);
+        Ok((maze, markers))
+    }
+
+    /// Replaces the maze's layout with walls loaded from somewhere else
+    pub fn set_layout(&mut self, walls_h: Vec<Vec<bool>>, walls_v: Vec<Vec<bool>>) {
+        self.height = walls_v.len();
+        self.width = walls_h[0].len();
+        self.cells = vec![vec![false; self.height]; self.width];
+        self.walls_h = walls_h;
+        self.walls_v = walls_v;
+        self.start_cell = Cell::from(0, 0);
+        self.end_cell = Cell::from(self.width - 1, self.height - 1);
+    }
+}
+
+#[test]
+/// The same seed has to build the same maze, also after a reset
+fn test_seed_reproducible() {
+    let mut maze1 = Maze::new(8, 5, 1234);
+    let mut maze2 = Maze::new(8, 5, 1234);
+    maze1.build(MazeAlgorithm::Backtracker);
+    maze2.build(MazeAlgorithm::Backtracker);
+    assert_eq!(maze1.walls_h, maze2.walls_h);
+    assert_eq!(maze1.walls_v, maze2.walls_v);
+
+    let (walls_h, walls_v) = (maze1.walls_h.clone(), maze1.walls_v.clone());
+    maze1.reset();
+    maze1.build(MazeAlgorithm::Backtracker);
+    assert_eq!(maze1.walls_h, walls_h);
+    assert_eq!(maze1.walls_v, walls_v);
+}
+
+#[test]
+/// Guards the exact layout generated from a known seed
+fn test_seed_layout() {
+    let mut maze = Maze::new(4, 3, 42);
+    maze.build(MazeAlgorithm::Backtracker);
+    assert_eq!(maze.walls_h, vec![
+        vec![true,  true,  true,  true ],
+        vec![false, true,  true,  false],
+        vec![true,  true,  true,  false],
+        vec![true,  true,  true,  true ],
+    ]);
+    assert_eq!(maze.walls_v, vec![
+        vec![true, false, false, false, true],
+        vec![true, false, false, true,  true],
+        vec![true, false, false, false, true],
+    ]);
+    assert_eq!((maze.start_cell.col, maze.start_cell.row), (0, 0));
+    assert_eq!((maze.end_cell.col, maze.end_cell.row), (3, 2));
+    // This one happens to be a single corridor
+    assert_eq!(maze.metrics(), MazeMetrics {
+        dead_ends: 2,
+        loops: 0,
+        average_corridor_length: 11.0,
+        longest_corridor: 11,
+    });
+}
+
+#[test]
+/// Every algorithm has to generate a maze where every cell can be reached
+fn test_algorithms_reachable() {
+    use MazeAlgorithm::*;
+    for &algorithm in [Backtracker, Prim, Kruskal, Wilson, Eller, RecursiveDivision].iter() {
+        for seed in 0..20 {
+            let mut maze = Maze::new(9, 6, seed);
+            maze.build(algorithm);
+            // Flood fill from the start cell
+            let mut reached = vec![vec![false; maze.height]; maze.width];
+            let mut stack = vec![maze.start_cell];
+            reached[0][0] = true;
+            while let Some(cell) = stack.pop() {
+                for next in maze.open_neighbors(&cell) {
+                    if !reached[next.col][next.row] {
+                        reached[next.col][next.row] = true;
+                        stack.push(next);
+                    }
+                }
+            }
+            assert!(reached.iter().flatten().all(|&r| r), "{:?} with seed {} left cells unreachable", algorithm, seed);
         }
-        self.paint_row(true, self.width);
     }
 }
+
+#[test]
+/// Braiding turns dead ends into loops
+fn test_braid() {
+    let mut maze = Maze::new(8, 5, 7);
+    maze.build(MazeAlgorithm::Backtracker);
+    let perfect = maze.metrics();
+    assert_eq!(perfect.loops, 0);
+    assert!(perfect.dead_ends > 0);
+
+    maze.braid(0.0);
+    assert_eq!(maze.metrics(), perfect);
+
+    maze.braid(1.0);
+    let braided = maze.metrics();
+    assert_eq!(braided.dead_ends, 0);
+    assert!(braided.loops > 0);
+
+    let mut maze = Maze::new(8, 5, 7);
+    maze.build(MazeAlgorithm::Kruskal);
+    maze.limit_dead_ends(3);
+    assert!(maze.metrics().dead_ends <= 3);
+}
+
+#[test]
+/// Mazes written with to_ascii have to be read back the same, with markers in their cells
+fn test_ascii_layout() {
+    let mut maze = Maze::new(6, 4, 99);
+    maze.build(MazeAlgorithm::Wilson);
+    let (read, markers) = Maze::from_ascii(&maze.to_ascii()).unwrap();
+    assert_eq!((read.width, read.height), (6, 4));
+    assert_eq!(read.walls_h, maze.walls_h);
+    assert_eq!(read.walls_v, maze.walls_v);
+    assert!(markers.is_empty());
+
+    let layout = "
++---+---+---+
+| 1   *     |
++   +---+   +
+|   |     2 |
++---+---+---+
+";
+    let (read, markers) = Maze::from_ascii(layout).unwrap();
+    assert_eq!(read.walls_h[1], vec![false, true, false]);
+    assert_eq!(read.walls_v[1], vec![true, true, false, true]);
+    assert_eq!(markers, vec![('1', Cell::from(0, 0)), ('*', Cell::from(1, 0)), ('2', Cell::from(2, 1))]);
+
+    assert!(Maze::from_ascii("+---+\n|   |\n+-x-+").is_err());
+}
+
+#[test]
+/// Paths follow the passages and take the shortest way around loops
+fn test_find_path() {
+    // The maze from test_seed_layout
+    let mut maze = Maze::new(4, 3, 42);
+    maze.build(MazeAlgorithm::Backtracker);
+    let path = maze.find_path(&maze.start_cell, &maze.end_cell).unwrap();
+    assert_eq!(path.len(), 6);
+    assert_eq!((path[0], path[5]), (maze.start_cell, maze.end_cell));
+    for step in path.windows(2) {
+        assert!(maze.open_neighbors(&step[0]).contains(&step[1]));
+    }
+
+    let (maze, _) = Maze::from_ascii("
++---+---+---+
+|           |
++   +---+   +
+|           |
++---+---+---+
+").unwrap();
+    assert_eq!(maze.find_path(&Cell::from(0, 0), &Cell::from(2, 1)).unwrap().len(), 4);
+    assert_eq!(maze.find_path(&Cell::from(1, 0), &Cell::from(1, 0)).unwrap(), vec![Cell::from(1, 0)]);
+    assert!(maze.find_path(&Cell::from(0, 0), &Cell::from(5, 5)).is_none());
+}
@@ -1,5 +1,6 @@
 pub mod mazegen;
 pub mod color;
+pub mod levelmap;
 
 use amethyst::assets::Handle;
 use amethyst::renderer::SpriteSheet;
@@ -1,41 +1,201 @@
-use amethyst::ecs::Entity;
+pub mod ricochet;
 
-#[derive(Clone)]
-#[allow(dead_code)]     // When we use the random() method everywhere, the compiler tells us the variants are never constructed
+use ncollide2d as nc;
+use nalgebra as na;
+use amethyst::ecs::{Component, DenseVecStorage, Entity, LazyUpdate, World, WorldExt};
+use crate::tank::{Tank, TankState};
+use crate::physics::Physics;
+
+/// The weapons tanks can hold. The state of each weapon lives in its own component,
+/// so that every weapon system only needs to read the `Tank`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Weapon {
-    Beamer {
-        heating_progress: f32,
-        shooting_timer: Option<f32>,
-        overheat_timer: Option<f32>,
-        heating_square: Option<Entity>,
-        beam: Option<Entity>,
-    },
-    Cannon {
-        shooting_timer: Option<f32>,
-    },
-    Rocket {
-        shooting_timer: Option<f32>,
-    },
-    _Railgun,
-    _Shotgun,
-}
-
-/*impl Weapon {
-    pub fn random() -> Self {
-        let mut rand = thread_rng();
-        let num = rand.gen_range(0, 5);
-        match num {
-            0 => Self::Beamer { heating_progress: 0.0, shooting_timer: None, overheat_timer: None, heating_square: None, beam: None },
-            1..=4 => Self::Cannon { shooting_timer: None },
-            _ => Self::default(),
-        }
-    }
-}*/
+    Cannon,
+    Beamer,
+    Rocket,
+    Railgun,
+    Shotgun,
+    Mine,
+    Frag,
+}
 
 impl Default for Weapon {
     fn default() -> Self {
-        Self::Cannon {
-            shooting_timer: None,
+        Weapon::Cannon
+    }
+}
+
+impl Weapon {
+    /// Give the weapon to a tank, replacing the one it had.
+    /// Happens at the end of the frame, so that the caller doesn't need to write every weapon's storage
+    pub fn equip(self, tank: Entity, lazy: &LazyUpdate) {
+        lazy.exec_mut(move |world| self.equip_now(tank, world));
+    }
+
+    fn equip_now(self, tank: Entity, world: &mut World) {
+        world.write_storage::<CannonState>().remove(tank);
+        // The beam and the heating square belong to the beamer, so they go with it.
+        // They may already be gone if the level was reset, so errors are ignored
+        let beamer = world.write_storage::<BeamerState>().remove(tank);
+        if let Some(beamer) = beamer {
+            let entities = world.entities();
+            for entity in beamer.heating_square.into_iter().chain(beamer.beam) {
+                let _ = entities.delete(entity);
+            }
+        }
+        world.write_storage::<RocketState>().remove(tank);
+        world.write_storage::<RailgunState>().remove(tank);
+        world.write_storage::<ShotgunState>().remove(tank);
+        world.write_storage::<MineState>().remove(tank);
+        // A bomb that is already flying is left alone, it bursts when its fuse runs out
+        world.write_storage::<FragState>().remove(tank);
+        let inserted = match self {
+            Weapon::Cannon => world.write_storage().insert(tank, CannonState::default()).map(|_| ()),
+            Weapon::Beamer => world.write_storage().insert(tank, BeamerState::default()).map(|_| ()),
+            Weapon::Rocket => world.write_storage().insert(tank, RocketState::default()).map(|_| ()),
+            Weapon::Railgun => world.write_storage().insert(tank, RailgunState::default()).map(|_| ()),
+            Weapon::Shotgun => world.write_storage().insert(tank, ShotgunState::default()).map(|_| ()),
+            Weapon::Mine => world.write_storage().insert(tank, MineState::default()).map(|_| ()),
+            Weapon::Frag => world.write_storage().insert(tank, FragState::default()).map(|_| ()),
+        };
+        // The tank might have been deleted before the end of the frame
+        if inserted.is_ok() {
+            if let Some(tank) = world.write_storage::<Tank>().get_mut(tank) {
+                tank.weapon = self;
+            }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Checks if a tank is pushed against something (like a wall or another tank),
+/// so that a projectile appearing `distance` in front of the tank's origin would be inside of it
+pub fn barrel_blocked(physics: &Physics, tank_pose: &na::Isometry2<f32>, distance: f32) -> bool {
+    // Trace a ray from the tank's origin in the direction it's facing
+    let ray = nc::query::Ray {
+        origin: tank_pose.translation.vector.into(),
+        dir: tank_pose.rotation * na::Vector2::new(0.0, 1.0)
+    };
+    let interferences = physics.geom_world.interferences_with_ray(
+        &physics.colliders,
+        &ray,
+        distance,
+        &nc::pipeline::object::CollisionGroups::new()
+    ).count();  // We only care about the number of the interactions, so we count items in the iterator
+
+    // The ray always intersects with the tank. We could compute the origin to be at the end of the tank's barrel,
+    // but it's much less expensive to just take the tank's origin.
+    interferences > 1
+}
+
+/// Mark a tank as hit by a shot, so that it gets destroyed.
+/// Happens at the end of the frame, so that weapon systems don't need to write tanks
+pub fn hit_tank(tank: Entity, by: Projectile, lazy: &LazyUpdate) {
+    lazy.exec_mut(move |world| {
+        if let Some(tank) = world.write_storage::<Tank>().get_mut(tank) {
+            if tank.state == TankState::Alive {
+                tank.state = TankState::Hit;
+                tank.hit_by = Some(by);
+            }
+        }
+    });
+}
+
+/// A Component for everything a tank shot, so that we know who destroyed a tank with what.
+/// Projectiles of weapons with limited ammo count against it until they are removed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Projectile {
+    /// The tank that shot the projectile
+    pub owner: Entity,
+    /// The weapon that shot it, every weapon has its own ammo
+    pub weapon: Weapon,
+}
+
+impl Component for Projectile {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// How many of the projectiles were shot by the tank with the weapon
+pub fn live_projectiles<'a>(owner: Entity, weapon: Weapon, projectiles: impl Iterator<Item = &'a Projectile>) -> usize {
+    projectiles.filter(|projectile| projectile.owner == owner && projectile.weapon == weapon).count()
+}
+
+/// State of a tank's cannon, shooting bouncing bullets
+#[derive(Default)]
+pub struct CannonState {
+    pub shooting_timer: Option<f32>,
+}
+
+impl Component for CannonState {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// State of a tank's beamer, shooting a laser beam after heating up
+#[derive(Default)]
+pub struct BeamerState {
+    pub heating_progress: f32,
+    pub shooting_timer: Option<f32>,
+    pub overheat_timer: Option<f32>,
+    pub heating_square: Option<Entity>,
+    /// Segments of the beam, one after every reflection off a wall
+    pub beam: Vec<Entity>,
+}
+
+impl Component for BeamerState {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// State of a tank's rocket launcher, shooting rockets that find their way to enemies
+#[derive(Default)]
+pub struct RocketState {
+    pub shooting_timer: Option<f32>,
+}
+
+impl Component for RocketState {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// State of a tank's railgun, firing a reflecting ray after charging up
+#[derive(Default)]
+pub struct RailgunState {
+    /// Time until the railgun fires, while it's charging
+    pub charge_timer: Option<f32>,
+    pub shooting_timer: Option<f32>,
+}
+
+impl Component for RailgunState {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// State of a tank's shotgun, firing a cone of short-lived pellets
+#[derive(Default)]
+pub struct ShotgunState {
+    pub shooting_timer: Option<f32>,
+}
+
+impl Component for ShotgunState {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// State of a tank's mine layer, dropping mines behind the tank
+#[derive(Default)]
+pub struct MineState {
+    pub shooting_timer: Option<f32>,
+}
+
+impl Component for MineState {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// State of a tank's frag bomb launcher, firing bombs that burst into fragments
+#[derive(Default)]
+pub struct FragState {
+    pub shooting_timer: Option<f32>,
+    /// The bomb in flight, pressing fire again bursts it
+    pub bomb: Option<Entity>,
+    /// Whether fire was held in the last frame, so that holding it doesn't burst the bomb right away
+    pub fire_held: bool,
+}
+
+impl Component for FragState {
+    type Storage = DenseVecStorage<Self>;
+}
@@ -0,0 +1,292 @@
+use nphysics2d as np;
+use ncollide2d as nc;
+use nalgebra as na;
+use crate::physics::Physics;
+use crate::config::{TankConfig, CannonConfig, ShotgunConfig};
+
+/// Where a bullet shot by a tank with the given pose appears, facing where it flies
+pub fn muzzle_pose(tank_pose: &na::Isometry2<f32>, tank_config: &TankConfig, cannon_config: &CannonConfig) -> na::Isometry2<f32> {
+    na::Isometry2::new(
+        tank_pose.translation.vector + tank_pose.rotation * na::Vector2::new(0.0, (tank_config.size_y as f32 / 2.0) + cannon_config.self_safety_margin),
+        tank_pose.rotation.angle(),
+    )
+}
+
+/// How a bouncing bullet is built, taken from the config of the weapon that shoots it
+#[derive(Clone, Copy, Debug)]
+pub struct BulletDesc {
+    pub radius: f32,
+    pub velocity: f32,
+    pub restitution: f32,
+    pub margin: f32,
+    pub density: f32,
+}
+
+impl From<&CannonConfig> for BulletDesc {
+    fn from(cannon_config: &CannonConfig) -> Self {
+        BulletDesc {
+            radius: cannon_config.bullet_radius,
+            velocity: cannon_config.bullet_velocity,
+            restitution: cannon_config.bullet_restitution,
+            margin: cannon_config.bullet_margin,
+            density: cannon_config.bullet_density,
+        }
+    }
+}
+
+impl From<&ShotgunConfig> for BulletDesc {
+    fn from(shotgun_config: &ShotgunConfig) -> Self {
+        BulletDesc {
+            radius: shotgun_config.pellet_radius,
+            velocity: shotgun_config.pellet_velocity,
+            restitution: shotgun_config.pellet_restitution,
+            margin: shotgun_config.pellet_margin,
+            density: shotgun_config.pellet_density,
+        }
+    }
+}
+
+/// Adds the body and the collider of a bouncing bullet flying forward from the given pose
+pub fn add_bullet(
+    physics: &mut Physics,
+    pose: na::Isometry2<f32>,
+    bullet: BulletDesc,
+) -> (np::object::DefaultBodyHandle, np::object::DefaultColliderHandle) {
+    let vel_vec = pose.rotation * na::Vector2::new(0.0, bullet.velocity);
+    let velocity = np::algebra::Velocity2::new(
+        vel_vec,
+        5.0,   // Add a spin to the bullet - fixes some errors with zero-angle collisions 
+    );
+    let shape = nc::shape::ShapeHandle::new(nc::shape::Ball::new(bullet.radius));
+    let body = np::object::RigidBodyDesc::new()
+        .position(pose)
+        .velocity(velocity)
+        .build();
+    let body_handle = physics.add_rigid_body(body);
+    let collider = np::object::ColliderDesc::new(shape)
+        .material(np::material::MaterialHandle::new(
+            // We use a contact model that doesn't calculate friction either way
+            np::material::BasicMaterial::new(bullet.restitution, 0.0))
+        )
+        .ccd_enabled(true)
+        .margin(bullet.margin)
+        .density(bullet.density)
+        .build(np::object::BodyPartHandle(body_handle, 0));
+    let collider_handle = physics.add_collider(collider);
+    (body_handle, collider_handle)
+}
+
+/// Predicts the path of a bullet shot by a tank with the given pose, bouncing off walls at most `bounces` times.
+/// Returns the points where the bullet appears, bounces and disappears (when it runs out of time or bounces).
+///
+/// Only static solid colliders (walls) are taken into account, the bullet flies straight through tanks and other bullets.
+/// Every bounce loses some of the bullet's speed depending on `bullet_restitution`, so the bullet gets less far in its lifetime
+pub fn bullet_path(
+    physics: &Physics,
+    tank_pose: &na::Isometry2<f32>,
+    tank_config: &TankConfig,
+    cannon_config: &CannonConfig,
+    bounces: usize,
+) -> Vec<na::Point2<f32>> {
+    let muzzle = muzzle_pose(tank_pose, tank_config, cannon_config);
+    let mut position: na::Point2<f32> = muzzle.translation.vector.into();
+    let mut velocity = muzzle.rotation * na::Vector2::new(0.0, cannon_config.bullet_velocity);
+    let mut time_left = cannon_config.bullet_time;
+
+    let mut path = vec![position];
+    for bounce in 0..=bounces {
+        let speed = velocity.norm();
+        if speed <= 0.0 || time_left <= 0.0 { break; }
+        let direction = velocity / speed;
+        let max_distance = speed * time_left;
+
+        // Cast a ray from the bullet's center, direction is normalized so the time of impact is a distance
+        let ray = nc::query::Ray::new(position, direction);
+        let hit = physics.geom_world.interferences_with_ray(
+            &physics.colliders,
+            &ray,
+            max_distance,
+            &nc::pipeline::object::CollisionGroups::new()
+        )
+            .filter(|(_, collider, _)| !collider.is_sensor() && is_static(physics, collider.body()))
+            .min_by(|a, b| a.2.toi.partial_cmp(&b.2.toi).unwrap())
+            .map(|(_, _, intersection)| intersection);
+
+        match hit {
+            Some(intersection) if bounce < bounces => {
+                let normal = if intersection.normal.dot(&direction) > 0.0 { -intersection.normal } else { intersection.normal };
+                // The bullet is a ball, so it bounces when its edge touches the wall, a bit before its center would
+                let along_normal = -direction.dot(&normal);
+                let distance = (intersection.toi - cannon_config.bullet_radius / along_normal.max(std::f32::EPSILON)).max(0.0);
+                position += direction * distance;
+                time_left -= distance / speed;
+                path.push(position);
+                // Reflect the velocity, restitution only slows the part of it going into the wall
+                velocity -= normal * velocity.dot(&normal) * (1.0 + cannon_config.bullet_restitution);
+            },
+            _ => {
+                // Either the bullet doesn't hit anything before it disappears,
+                // or we don't care about what happens after the last bounce
+                let distance = match hit {
+                    Some(intersection) => (intersection.toi - cannon_config.bullet_radius).max(0.0),
+                    None => max_distance,
+                };
+                path.push(position + direction * distance);
+                break;
+            },
+        }
+    }
+    path
+}
+
+/// Follows a ray that reflects off walls at most `reflections` times, until it hits a collider `stops_at` accepts
+/// or it gets `range` far. Returns the points where the ray starts, reflects and ends, and the collider that stopped it.
+///
+/// Sensors and everything that moves but isn't accepted by `stops_at` are ignored, so is the `ignored` collider
+/// until the first reflection (so that a tank doesn't hit itself the moment it fires)
+pub fn ray_path<F: Fn(np::object::DefaultColliderHandle) -> bool>(
+    physics: &Physics,
+    start: na::Point2<f32>,
+    direction: na::Vector2<f32>,
+    reflections: usize,
+    range: f32,
+    ignored: Option<np::object::DefaultColliderHandle>,
+    stops_at: F,
+) -> (Vec<na::Point2<f32>>, Option<np::object::DefaultColliderHandle>) {
+    let mut position = start;
+    let mut direction = direction.normalize();
+    let mut range_left = range;
+
+    let mut path = vec![position];
+    for reflection in 0..=reflections {
+        let ray = nc::query::Ray::new(position, direction);
+        let hit = physics.geom_world.interferences_with_ray(
+            &physics.colliders,
+            &ray,
+            range_left,
+            &nc::pipeline::object::CollisionGroups::new()
+        )
+            .filter(|(handle, collider, _)| !collider.is_sensor() && (reflection > 0 || Some(*handle) != ignored))
+            .filter(|(handle, collider, _)| stops_at(*handle) || is_static(physics, collider.body()))
+            .min_by(|a, b| a.2.toi.partial_cmp(&b.2.toi).unwrap())
+            .map(|(handle, _, intersection)| (handle, intersection));
+
+        let (handle, intersection) = match hit {
+            Some(hit) => hit,
+            None => {
+                path.push(position + direction * range_left);
+                return (path, None);
+            },
+        };
+        // The direction is normalized, so the time of impact is a distance
+        position += direction * intersection.toi;
+        path.push(position);
+        if stops_at(handle) {
+            return (path, Some(handle));
+        }
+
+        // Bounce off the wall
+        range_left -= intersection.toi;
+        let normal = if intersection.normal.dot(&direction) > 0.0 { -intersection.normal } else { intersection.normal };
+        direction -= normal * direction.dot(&normal) * 2.0;
+        // Start a bit away from the wall, so that the next ray doesn't hit it again right away
+        position += normal * 0.01;
+    }
+    (path, None)
+}
+
+fn is_static(physics: &Physics, handle: np::object::DefaultBodyHandle) -> bool {
+    use np::object::Body;
+    physics.get_rigid_body(handle)
+        .map_or(false, |rb| rb.status() == np::object::BodyStatus::Static)
+}
+
+#[test]
+/// A bullet shot at a wall comes straight back, one shot at an angle keeps going sideways
+fn test_bullet_path() {
+    use amethyst::config::Config;
+
+    let tank_config = TankConfig::load("res/config/tank.ron").unwrap();
+    let mut cannon_config = CannonConfig::load("res/config/cannon.ron").unwrap();
+    cannon_config.bullet_restitution = 1.0;
+
+    // A single wall 100 units to the right of the origin
+    let mut physics = Physics::new();
+    let wall = physics.add_rigid_body(
+        np::object::RigidBodyDesc::new()
+            .translation(na::Vector2::new(100.0, 0.0))
+            .status(np::object::BodyStatus::Static)
+            .build()
+    );
+    physics.add_collider(
+        np::object::ColliderDesc::new(nc::shape::ShapeHandle::new(nc::shape::Cuboid::new(na::Vector2::new(4.0, 500.0))))
+            .build(np::object::BodyPartHandle(wall, 0))
+    );
+    // Stepping puts the wall's collider into the broad phase, so that rays can find it
+    physics.step();
+
+    // Tanks face up, turn the tank to the right
+    let facing_wall = na::Isometry2::new(na::Vector2::zeros(), -std::f32::consts::FRAC_PI_2);
+    let path = bullet_path(&physics, &facing_wall, &tank_config, &cannon_config, 1);
+    assert_eq!(path.len(), 3);
+    assert!((path[1].x - (96.0 - cannon_config.bullet_radius)).abs() < 0.01);
+    assert!(path[1].y.abs() < 0.01);
+    assert!(path[2].x < path[1].x);
+
+    // With no bounces the path stops at the wall
+    let path = bullet_path(&physics, &facing_wall, &tank_config, &cannon_config, 0);
+    assert_eq!(path.len(), 2);
+
+    // Shooting at 45 degrees, the bullet keeps going up after the bounce
+    let angled = na::Isometry2::new(na::Vector2::zeros(), -std::f32::consts::FRAC_PI_4);
+    let path = bullet_path(&physics, &angled, &tank_config, &cannon_config, 1);
+    assert_eq!(path.len(), 3);
+    assert!(path[2].x < path[1].x && path[2].y > path[1].y);
+
+    // Shooting away from the wall, the bullet flies for its whole lifetime
+    let away = na::Isometry2::new(na::Vector2::zeros(), std::f32::consts::FRAC_PI_2);
+    let path = bullet_path(&physics, &away, &tank_config, &cannon_config, 3);
+    assert_eq!(path.len(), 2);
+    assert!((na::distance(&path[0], &path[1]) - cannon_config.bullet_velocity * cannon_config.bullet_time).abs() < 0.1);
+}
+
+#[test]
+/// A ray reflects off a wall and stops at the first collider it's looking for
+fn test_ray_path() {
+    let mut physics = Physics::new();
+    let mut add_box = |x: f32, y: f32, half_width: f32, half_height: f32, status: np::object::BodyStatus| {
+        let body = physics.add_rigid_body(
+            np::object::RigidBodyDesc::new()
+                .translation(na::Vector2::new(x, y))
+                .status(status)
+                .build()
+        );
+        physics.add_collider(
+            np::object::ColliderDesc::new(nc::shape::ShapeHandle::new(nc::shape::Cuboid::new(na::Vector2::new(half_width, half_height))))
+                .build(np::object::BodyPartHandle(body, 0))
+        )
+    };
+    // A wall to the right, and a target below the ray's start that can only be hit after reflecting
+    let _wall = add_box(100.0, 0.0, 4.0, 500.0, np::object::BodyStatus::Static);
+    let target = add_box(0.0, -100.0, 10.0, 10.0, np::object::BodyStatus::Dynamic);
+    physics.step();
+
+    // Shooting right and a bit down, the ray comes back from the wall towards the target
+    let start = na::Point2::new(0.0, 0.0);
+    let direction = na::Vector2::new(96.0, -50.0);
+    let (path, hit) = ray_path(&physics, start, direction, 1, 1000.0, None, |handle| handle == target);
+    assert_eq!(hit, Some(target));
+    assert_eq!(path.len(), 3);
+    assert!((path[1].x - 96.0).abs() < 0.01);
+    assert!((path[1].y + 50.0).abs() < 0.01);
+
+    // Without reflections the ray stops at the wall
+    let (path, hit) = ray_path(&physics, start, direction, 0, 1000.0, None, |handle| handle == target);
+    assert_eq!(hit, None);
+    assert_eq!(path.len(), 2);
+
+    // The ray can't get further than its range
+    let (path, hit) = ray_path(&physics, start, na::Vector2::new(-1.0, 0.0), 3, 50.0, None, |handle| handle == target);
+    assert_eq!(hit, None);
+    assert!((path[1].x + 50.0).abs() < 0.01);
+}